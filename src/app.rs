@@ -1,12 +1,25 @@
 mod render_engine;
 mod data;
 mod file_io;
+mod glsl;
+mod inspector;
+mod keyboard;
+mod profiler;
+mod render_graph;
 mod ui;
 
 use data::*;
 use crate::{RELOAD_DEBOUNCE_MS, DEFAULT_SHADER_PATH, DEFAULT_POST_SHADER_PATH, FILE_CHECK_TIMEOUT_MS};
 
 use render_engine::ShaderRenderer;
+use inspector::{PixelInspector, PixelRequest, ReadSource};
+use keyboard::{KeyboardState, KEYBOARD_UNIFORM};
+use file_io::FeedbackReplay;
+use profiler::{BenchmarkResult, BenchmarkRun, PassTimer, Profiler, HUD_HISTORY};
+use render_graph::{
+    FramePlan, PassTargets, PostChain, Present, RenderGraph, ShaderPass, TargetSpec,
+    BUFFER_NAMES, DOWNSAMPLE_SHADER, MAIN_PASS_NAME, OUTPUT_TARGET_NAME, POST_SOURCE_NAME, PRESENT_SHADER, PREVIEW_TARGET_NAME,
+};
 
 use std::collections::HashMap;
 use std::sync::{mpsc, Arc};
@@ -24,6 +37,22 @@ pub struct ShaderApp {
     shader_renderer: Arc<Mutex<ShaderRenderer>>,
     time: f32,
     auto_time: bool,
+    time_delta: f32,
+    frame_count: u32,
+    mouse: MouseState,
+    /// Physical size of the preview area the mouse coordinates refer to
+    canvas_resolution: egui::Vec2,
+    /// Preview render size relative to the canvas, 0.25..=2.0
+    render_scale: f32,
+    /// Preview renders at `export_resolution`, letterboxed into the canvas
+    fixed_resolution: bool,
+    /// Overlay with frame and per-pass GPU timings
+    show_hud: bool,
+    profiler: Arc<Mutex<Profiler>>,
+    /// Pixel values under the pointer, per stage of the frame
+    inspector: PixelInspector,
+    keyboard: KeyboardState,
+    shadertoy_mode: bool,
     shader_error: Arc<Mutex<Option<String>>>,
     watcher: Option<RecommendedWatcher>,
     shader_update_receiver: mpsc::Receiver<()>,
@@ -31,25 +60,40 @@ pub struct ShaderApp {
     uniforms: HashMap<String, UniformInfo>,
     current_shader_path: PathBuf,
     export_resolution: [u32; 2],
+    /// Export renders at this multiple of `export_resolution` and filters down
+    export_supersampling: u32,
+    export_filter: DownsampleFilter,
+    downsample_renderer: Arc<Mutex<ShaderRenderer>>,
+    /// GL_MAX_TEXTURE_SIZE, larger exports are rendered in tiles
+    max_texture_size: u32,
     video_duration_frames: u32,
     video_fps: u32,
+    /// `u_date` at time zero of every export, so exports don't depend on the wall clock
+    export_date: [f32; 4],
     ffmpeg_available: bool,
     export_progress: Arc<Mutex<Option<ExportProgress>>>,
+    /// Image export replaying feedback frames, advanced every UI frame
+    image_replay: Option<FeedbackReplay>,
+    /// Frames rendered by the benchmark at export resolution
+    benchmark_frames: u32,
+    benchmark_result: Option<BenchmarkResult>,
+    /// Benchmark in progress, advanced every UI frame
+    benchmark_run: Option<BenchmarkRun>,
     
-    // Post-processing
-    post_process_enabled: bool,
-    post_process_shader_path: Option<PathBuf>,
-    post_process_renderer: Option<Arc<Mutex<ShaderRenderer>>>,
-    post_process_uniforms: HashMap<String, UniformInfo>,
-    post_process_error: Arc<Mutex<Option<String>>>,
-    post_process_watcher: Option<RecommendedWatcher>,
-    post_process_update_receiver: Option<mpsc::Receiver<()>>,
-    post_process_last_reload: Instant,
+    // Render target format of the main shader, and how the final image is output
+    main_format: TargetFormat,
+    output: OutputSettings,
+    present_renderer: Arc<Mutex<ShaderRenderer>>,
     
-    // Intermediate framebuffer
-    intermediate_fbo: Option<glow::Framebuffer>,
-    intermediate_texture: Option<glow::Texture>,
-    intermediate_size: (u32, u32),
+    // Post-processing chain, applied in order after the main shader
+    post_stages: Vec<ShaderPass>,
+    next_post_stage: usize,
+    post_shader_dir: PathBuf,
+    
+    // Buffer passes rendered before the main shader
+    buffers: Vec<ShaderPass>,
+    pass_targets: PassTargets,
+    graph_error: Arc<Mutex<Option<String>>>,
 }
 
 impl ShaderApp {
@@ -59,13 +103,27 @@ impl ShaderApp {
         let shader_path = get_default_shader_path(DEFAULT_SHADER_PATH);
         let post_shader_path = get_default_shader_path(DEFAULT_POST_SHADER_PATH);
 
+        // Shadertoy mode starts on, and the first load wraps exactly like a reload
+        let shadertoy_mode = true;
         let initial_shader_source = std::fs::read_to_string(&shader_path)
             .expect("Failed to read fragment shader on startup");
-        
-        let detected_uniforms = parse_uniforms(&initial_shader_source);
+        let initial_shader_source = prepare_shader_source(initial_shader_source, shadertoy_mode);
         
         let shader_renderer = ShaderRenderer::new(&gl, &initial_shader_source)
             .expect("Failed to compile initial shader");
+        let detected_uniforms = reconcile_uniforms(
+            MAIN_PASS_NAME,
+            parse_uniforms(&initial_shader_source),
+            &shader_renderer,
+        );
+        let present_renderer = ShaderRenderer::new(&gl, PRESENT_SHADER)
+            .expect("Failed to compile output shader");
+        let downsample_renderer = ShaderRenderer::new(&gl, DOWNSAMPLE_SHADER)
+            .expect("Failed to compile downsample shader");
+        let max_texture_size = unsafe {
+            use glow::HasContext as _;
+            gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE).max(1) as u32
+        };
 
         let (tx, rx) = mpsc::channel();
         let watcher = Self::create_watcher(&shader_path, tx);
@@ -84,6 +142,17 @@ impl ShaderApp {
             shader_renderer: Arc::new(Mutex::new(shader_renderer)),
             time: 0.0,
             auto_time: true,
+            time_delta: 0.0,
+            frame_count: 0,
+            mouse: MouseState::default(),
+            canvas_resolution: egui::Vec2::ZERO,
+            render_scale: 1.0,
+            fixed_resolution: false,
+            show_hud: false,
+            profiler: Arc::new(Mutex::new(Profiler::new(HUD_HISTORY))),
+            inspector: PixelInspector::default(),
+            keyboard: KeyboardState::default(),
+            shadertoy_mode,
             shader_error: Arc::new(Mutex::new(None)),
             watcher,
             shader_update_receiver: rx,
@@ -91,25 +160,33 @@ impl ShaderApp {
             uniforms: detected_uniforms,
             current_shader_path: shader_path,
             export_resolution: [1920, 1080],
+            export_supersampling: 1,
+            export_filter: DownsampleFilter::default(),
+            downsample_renderer: Arc::new(Mutex::new(downsample_renderer)),
+            max_texture_size,
             video_duration_frames: 300,
             video_fps: 30,
+            export_date: DEFAULT_EXPORT_DATE,
             ffmpeg_available,
             export_progress: Arc::new(Mutex::new(None)),
+            image_replay: None,
+            benchmark_frames: 100,
+            benchmark_result: None,
+            benchmark_run: None,
             
-            // Post-processing
-            post_process_enabled: false,
-            post_process_shader_path: Some(post_shader_path),
-            post_process_renderer: None,
-            post_process_uniforms: HashMap::new(),
-            post_process_error: Arc::new(Mutex::new(None)),
-            post_process_watcher: None,
-            post_process_update_receiver: None,
-            post_process_last_reload: Instant::now(),
+            main_format: TargetFormat::default(),
+            output: OutputSettings::default(),
+            present_renderer: Arc::new(Mutex::new(present_renderer)),
             
-            // Intermediate framebuffer
-            intermediate_fbo: None,
-            intermediate_texture: None,
-            intermediate_size: (0, 0),
+            // Post-processing chain
+            post_stages: Vec::new(),
+            next_post_stage: 0,
+            post_shader_dir: post_shader_path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            
+            // Buffer passes
+            buffers: BUFFER_NAMES.iter().map(|name| ShaderPass::new(name)).collect(),
+            pass_targets: PassTargets::default(),
+            graph_error: Arc::new(Mutex::new(None)),
         })
     }
 
@@ -139,10 +216,12 @@ impl ShaderApp {
 
         match std::fs::read_to_string(&self.current_shader_path) {
             Ok(shader_source) => {
+                let shader_source = self.prepare_source(shader_source);
                 let new_uniforms = parse_uniforms(&shader_source);
                 
                 match ShaderRenderer::new(&self.gl, &shader_source) {
                     Ok(new_renderer) => {
+                        let new_uniforms = reconcile_uniforms(MAIN_PASS_NAME, new_uniforms, &new_renderer);
                         {
                             let mut renderer_guard = self.shader_renderer.lock();
                             renderer_guard.destroy(&self.gl);
@@ -166,60 +245,26 @@ impl ShaderApp {
         }
     }
 
-    pub fn load_post_process_shader(&mut self, path: PathBuf) {
-        if let Some(watcher) = &mut self.post_process_watcher {
-            if let Some(old_path) = &self.post_process_shader_path {
-                let _ = watcher.unwatch(old_path);
-            }
-        }
-
-        self.post_process_shader_path = Some(path.clone());
-
-        let (tx, rx) = mpsc::channel();
-        self.post_process_watcher = Self::create_watcher(&path, tx);
-        self.post_process_update_receiver = Some(rx);
-
-        match std::fs::read_to_string(&path) {
-            Ok(shader_source) => {
-                let new_uniforms = parse_uniforms(&shader_source);
-                
-                match ShaderRenderer::new(&self.gl, &shader_source) {
-                    Ok(new_renderer) => {
-                        if let Some(old_renderer) = &self.post_process_renderer {
-                            old_renderer.lock().destroy(&self.gl);
-                        }
-                        
-                        self.post_process_renderer = Some(Arc::new(Mutex::new(new_renderer)));
-                        *self.post_process_error.lock() = None;
-                        self.post_process_uniforms = new_uniforms;
-                        log::info!("Post-process shader loaded: {:?}", path);
-                    }
-                    Err(e) => {
-                        *self.post_process_error.lock() = Some(e.clone());
-                        log::error!("Post-process shader compilation failed: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                let error_message = format!("Failed to read shader file: {}", e);
-                *self.post_process_error.lock() = Some(error_message);
-            }
-        }
-    }
-
     pub fn try_reload_shader(&mut self) {
         if self.last_reload.elapsed() < Duration::from_millis(RELOAD_DEBOUNCE_MS) {
             return;
         }
         
         log::info!("Shader file changed, attempting to reload...");
+        self.reload_shader();
+    }
+
+    /// Recompile the main shader from disk, keeping edited uniform values
+    pub fn reload_shader(&mut self) {
         
         match std::fs::read_to_string(&self.current_shader_path) {
             Ok(new_source) => {
+                let new_source = self.prepare_source(new_source);
                 let new_uniforms = parse_uniforms(&new_source);
                 
                 match ShaderRenderer::new(&self.gl, &new_source) {
                     Ok(new_renderer) => {
+                        let new_uniforms = reconcile_uniforms(MAIN_PASS_NAME, new_uniforms, &new_renderer);
                         {
                             let mut renderer_guard = self.shader_renderer.lock();
                             renderer_guard.destroy(&self.gl);
@@ -244,227 +289,386 @@ impl ShaderApp {
         }
     }
 
-    pub fn try_reload_post_process(&mut self) {
-        if self.post_process_last_reload.elapsed() < Duration::from_millis(RELOAD_DEBOUNCE_MS) {
+    /// Append a post-process stage that reads the output of the stage before it
+    pub fn add_post_stage(&mut self, path: PathBuf) {
+        self.next_post_stage += 1;
+        let mut stage = ShaderPass::new(&format!("Post {}", self.next_post_stage));
+        stage.load(&self.gl, path, self.shadertoy_mode);
+        self.post_stages.push(stage);
+    }
+
+    pub fn remove_post_stage(&mut self, index: usize) {
+        let mut stage = self.post_stages.remove(index);
+        stage.unload(&self.gl);
+    }
+
+    /// Feed this frame's key events into the keyboard texture and bind it to `u_keyboard`
+    pub fn update_keyboard(&mut self, ctx: &egui::Context) {
+        // Typing into a text field should not drive the shader
+        let capture = !ctx.wants_keyboard_input();
+        ctx.input(|i| self.keyboard.update(i, capture));
+
+        let in_use = self.uniform_maps().any(|uniforms| uniforms.contains_key(KEYBOARD_UNIFORM));
+        if !in_use {
             return;
         }
-        
-        if let Some(path) = &self.post_process_shader_path {
-            log::info!("Post-process shader file changed, attempting to reload...");
-            
-            match std::fs::read_to_string(path) {
-                Ok(new_source) => {
-                    let new_uniforms = parse_uniforms(&new_source);
-                    
-                    match ShaderRenderer::new(&self.gl, &new_source) {
-                        Ok(new_renderer) => {
-                            if let Some(old_renderer) = &self.post_process_renderer {
-                                old_renderer.lock().destroy(&self.gl);
-                            }
-                            
-                            self.post_process_renderer = Some(Arc::new(Mutex::new(new_renderer)));
-                            *self.post_process_error.lock() = None;
-                            self.merge_post_process_uniforms(new_uniforms);
-                            self.post_process_last_reload = Instant::now();
-                            log::info!("Post-process shader reloaded successfully!");
-                        }
-                        Err(e) => {
-                            *self.post_process_error.lock() = Some(e.clone());
-                            log::error!("Post-process shader compilation failed: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    let error_message = format!("Failed to read shader file: {}", e);
-                    *self.post_process_error.lock() = Some(error_message);
-                }
+
+        let handle = self.keyboard.upload(&self.gl);
+        for uniforms in self.uniform_maps_mut() {
+            if let Some(uniform) = uniforms.get_mut(KEYBOARD_UNIFORM)
+                && uniform.uniform_type == UniformType::Sampler2D
+            {
+                uniform.value = UniformValue::Sampler2D(handle.clone());
             }
         }
     }
 
-    pub fn merge_uniforms(&mut self, new_uniforms: HashMap<String, UniformInfo>) {
-        let mut merged = HashMap::new();
-        
-        for (name, new_info) in new_uniforms {
-            if let Some(old_info) = self.uniforms.get(&name) {
-                if old_info.uniform_type == new_info.uniform_type {
-                    merged.insert(name, old_info.clone());
-                    continue;
-                }
-            }
-            merged.insert(name, new_info);
+    /// Apply source transformations (Shadertoy wrapping) before compiling
+    fn prepare_source(&self, source: String) -> String {
+        prepare_shader_source(source, self.shadertoy_mode)
+    }
+
+    /// Hot-reload buffer and post-process shaders whose files changed
+    pub fn poll_pass_reloads(&mut self) {
+        for pass in self.buffers.iter_mut().chain(&mut self.post_stages) {
+            pass.poll_reload(&self.gl, self.shadertoy_mode);
         }
-        
-        self.uniforms = merged;
     }
 
-    pub fn merge_post_process_uniforms(&mut self, new_uniforms: HashMap<String, UniformInfo>) {
-        let mut merged = HashMap::new();
-        
-        for (name, new_info) in new_uniforms {
-            if let Some(old_info) = self.post_process_uniforms.get(&name) {
-                if old_info.uniform_type == new_info.uniform_type {
-                    merged.insert(name, old_info.clone());
-                    continue;
-                }
-            }
-            merged.insert(name, new_info);
+    /// Recompile every loaded buffer and post-process shader
+    pub fn reload_passes(&mut self) {
+        for pass in self.buffers.iter_mut().chain(&mut self.post_stages) {
+            pass.reload(&self.gl, self.shadertoy_mode);
         }
-        
-        self.post_process_uniforms = merged;
     }
 
-    fn ensure_intermediate_fbo(&mut self, width: u32, height: u32) {
-        use glow::HasContext as _;
-        
-        // Check if we need to recreate
-        if self.intermediate_size == (width, height) && 
-           self.intermediate_fbo.is_some() && 
-           self.intermediate_texture.is_some() {
-            return; // Already correct size
+    /// Uniforms of the main shader, the buffers and the post-process stages
+    fn uniform_maps(&self) -> impl Iterator<Item = &HashMap<String, UniformInfo>> {
+        std::iter::once(&self.uniforms)
+            .chain(self.buffers.iter().map(|pass| &pass.uniforms))
+            .chain(self.post_stages.iter().map(|stage| &stage.uniforms))
+    }
+
+    fn uniform_maps_mut(&mut self) -> impl Iterator<Item = &mut HashMap<String, UniformInfo>> {
+        std::iter::once(&mut self.uniforms)
+            .chain(self.buffers.iter_mut().map(|pass| &mut pass.uniforms))
+            .chain(self.post_stages.iter_mut().map(|stage| &mut stage.uniforms))
+    }
+
+    /// Snapshot the buffer passes for rendering into `targets`, remembering any ordering error
+    fn build_render_graph(&self, targets: &PassTargets) -> RenderGraph {
+        let (graph, error) = RenderGraph::build(&self.buffers, targets);
+        let mut graph_error = self.graph_error.lock();
+        if let Some(e) = &error
+            && graph_error.as_ref() != Some(e)
+        {
+            log::error!("{}", e);
         }
-        
-        unsafe {
-            // Clean up old resources
-            if let Some(fbo) = self.intermediate_fbo {
-                self.gl.delete_framebuffer(fbo);
-            }
-            if let Some(tex) = self.intermediate_texture {
-                self.gl.delete_texture(tex);
-            }
-            
-            // Create framebuffer
-            let fbo = self.gl.create_framebuffer().unwrap();
-            self.gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
-            
-            // Create texture
-            let texture = self.gl.create_texture().unwrap();
-            self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
-            self.gl.tex_image_2d(
-                glow::TEXTURE_2D, 0, glow::RGBA as i32,
-                width as i32, height as i32, 0,
-                glow::RGBA, glow::UNSIGNED_BYTE, None,
-            );
-            self.gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
-            self.gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
-            self.gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
-            self.gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
-            
-            // Attach to framebuffer
-            self.gl.framebuffer_texture_2d(
-                glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0,
-                glow::TEXTURE_2D, Some(texture), 0,
-            );
-            
-            // Check status
-            if self.gl.check_framebuffer_status(glow::FRAMEBUFFER) != glow::FRAMEBUFFER_COMPLETE {
-                log::error!("Intermediate framebuffer incomplete!");
-            }
-            
-            self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-            
-            self.intermediate_fbo = Some(fbo);
-            self.intermediate_texture = Some(texture);
-            self.intermediate_size = (width, height);
-            
-            log::info!("Created intermediate framebuffer: {}x{}", width, height);
+        *graph_error = error;
+        graph
+    }
+
+    /// Render targets the enabled passes need this frame
+    fn target_specs(&self) -> Vec<TargetSpec> {
+        let spec = |name: &str, feedback, format| TargetSpec { name: name.to_string(), feedback, format };
+
+        let mut specs: Vec<TargetSpec> = self.buffers.iter()
+            .filter(|pass| pass.is_active())
+            .map(|pass| spec(&pass.name, pass.uses_feedback(), pass.format))
+            .collect();
+        // Buffers sampling the Image pass read its previous frame, so it keeps one too
+        let main_sampled = self.buffers.iter()
+            .chain(&self.post_stages)
+            .any(|pass| pass.is_active() && pass.samples(MAIN_PASS_NAME));
+        if main_sampled || render_graph::uses_feedback(MAIN_PASS_NAME, &self.uniforms) {
+            specs.push(spec(MAIN_PASS_NAME, true, self.main_format));
+        }
+
+        // The main shader feeds the first stage, every stage but the last feeds the next
+        let stages: Vec<&ShaderPass> = self.post_stages.iter().filter(|stage| stage.is_active()).collect();
+        let mut final_format = self.main_format;
+        if let Some((last, rest)) = stages.split_last() {
+            specs.push(spec(POST_SOURCE_NAME, false, self.main_format));
+            specs.extend(rest.iter().map(|stage| spec(&stage.name, false, stage.format)));
+            final_format = last.format;
+        }
+
+        if self.output.needs_present() {
+            specs.push(spec(OUTPUT_TARGET_NAME, false, final_format));
+        }
+        specs
+    }
+
+    /// Snapshot every pass for drawing one frame into `targets`
+    fn frame_plan(&self, targets: &PassTargets) -> FramePlan {
+        let graph = self.build_render_graph(targets);
+        let mut main_uniforms = self.uniforms.clone();
+        graph.bind_inputs(&mut main_uniforms, MAIN_PASS_NAME);
+
+        FramePlan {
+            post_chain: PostChain::build(&self.post_stages, &graph, targets),
+            present: Present::build(&self.present_renderer, &self.output, targets),
+            graph,
+            main_renderer: self.shader_renderer.clone(),
+            main_uniforms,
+            timer: PassTimer::default(),
+        }
+    }
+
+    /// Clear feedback textures so simulations restart from black
+    pub fn reset_feedback(&self, pass_name: Option<&str>) {
+        self.pass_targets.clear(&self.gl, pass_name);
+    }
+
+    /// Built-in uniform values for the current frame at the given resolution
+    pub fn frame_inputs(&self, resolution: egui::Vec2) -> FrameInputs {
+        FrameInputs {
+            time: self.time,
+            time_delta: self.time_delta,
+            frame: self.frame_count,
+            frame_rate: if self.time_delta > 0.0 { 1.0 / self.time_delta } else { 0.0 },
+            resolution,
+            // Keep mouse coordinates in the same pixel space as the render target
+            mouse: if self.canvas_resolution.x > 0.0 && self.canvas_resolution.y > 0.0 {
+                self.mouse.scaled(resolution / self.canvas_resolution)
+            } else {
+                self.mouse
+            },
+            date: current_date(),
+            tile: [0.0; 4],
         }
     }
 
+    pub fn merge_uniforms(&mut self, new_uniforms: HashMap<String, UniformInfo>) {
+        self.uniforms = merge_uniform_state(&self.uniforms, new_uniforms);
+    }
+
     pub fn custom_painting(&mut self, ui: &mut egui::Ui) {
-        let (rect, _response) = ui.allocate_exact_size(ui.available_size(), egui::Sense::hover());
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), egui::Sense::drag());
+        
+        // Fixed resolution keeps the export's aspect ratio, letterboxed inside the canvas
+        let [export_width, export_height] = self.export_resolution;
+        let frame_rect = if self.fixed_resolution {
+            letterbox(rect, export_width as f32 / export_height as f32)
+        } else {
+            rect
+        };
 
-        if self.auto_time {
-            self.time += ui.input(|i| i.stable_dt);
+        // Drag-enabled vec2 uniforms follow the pointer while it is held on the canvas
+        if let Some(pos) = response.interact_pointer_pos() {
+            let uv = egui::vec2(
+                (pos.x - frame_rect.left()) / frame_rect.width(),
+                (frame_rect.bottom() - pos.y) / frame_rect.height(),
+            );
+            for uniform in self.uniform_maps_mut().flat_map(|uniforms| uniforms.values_mut()) {
+                if uniform.canvas_drag
+                    && let UniformValue::Vec2(vals) = &mut uniform.value
+                {
+                    *vals = uniform.meta.denormalize_xy(uv);
+                }
+            }
         }
-        let time = self.time;
+
+        let dt = ui.input(|i| i.stable_dt);
+        self.time_delta = dt;
         
         let pixels_per_point = ui.ctx().pixels_per_point();
         
-        let size = rect.size();
-        let width = (size.x * pixels_per_point) as u32;
-        let height = (size.y * pixels_per_point) as u32;
+        // Physical size and offset of the framed area within the canvas
+        let frame_width = (frame_rect.width() * pixels_per_point).round().max(1.0) as u32;
+        let frame_height = (frame_rect.height() * pixels_per_point).round().max(1.0) as u32;
+        let frame_offset = [
+            ((frame_rect.left() - rect.left()) * pixels_per_point).round() as i32,
+            ((rect.bottom() - frame_rect.bottom()) * pixels_per_point).round() as i32,
+        ];
         
-        let use_post_process = self.post_process_enabled && 
-                            self.post_process_renderer.is_some();
+        // Pointer in physical pixels, origin at the bottom-left of the framed area
+        let to_canvas = |pos: egui::Pos2| {
+            [
+                (pos.x - frame_rect.left()) * pixels_per_point,
+                (frame_rect.bottom() - pos.y) * pixels_per_point,
+            ]
+        };
+        let was_down = self.mouse.down;
+        self.mouse.down = response.is_pointer_button_down_on();
+        self.mouse.pressed = self.mouse.down && !was_down;
+        if self.mouse.down
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            let position = to_canvas(pos);
+            self.mouse.position = position;
+            self.mouse.hover = position;
+            if self.mouse.pressed {
+                self.mouse.click = position;
+            }
+        } else if let Some(pos) = response.hover_pos() {
+            self.mouse.hover = to_canvas(pos);
+        }
         
-        if use_post_process {
-            self.ensure_intermediate_fbo(width, height);
-            
-            let gl = self.gl.clone();
-            let shader_renderer = self.shader_renderer.clone();
-            let uniforms = self.uniforms.clone();
-            let post_renderer = self.post_process_renderer.clone().unwrap();
-            let mut post_uniforms = self.post_process_uniforms.clone();
-            let intermediate_fbo = self.intermediate_fbo.unwrap();
-            let intermediate_texture = self.intermediate_texture.unwrap();
-            
-            post_uniforms.insert(
-                "u_mainPass".to_string(),
-                UniformInfo {
-                    uniform_type: UniformType::Sampler2D,
-                    value: UniformValue::Sampler2D(Some(TextureHandle {
-                        path: PathBuf::from("[main_pass]"),
-                        texture_id: Some(intermediate_texture),
-                        width,
-                        height,
-                    })),
-                },
-            );
-            
-            let cb = egui_glow::CallbackFn::new(move |info, painter| {
-                use glow::HasContext as _;
-                let gl = painter.gl();
-                
-                // Get proper viewport from callback info
-                let viewport = info.viewport_in_pixels();
-                let vp_width = viewport.width_px;
-                let vp_height = viewport.height_px;
-                let vp_x = viewport.left_px;
-                let vp_y = viewport.from_bottom_px;
-                
-                unsafe {
-                    // === PASS 1: Render main shader to intermediate texture ===
-                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(intermediate_fbo));
-                    gl.viewport(0, 0, width as i32, height as i32);
-                    gl.clear_color(0.0, 0.0, 0.0, 1.0);
-                    gl.clear(glow::COLOR_BUFFER_BIT);
-                    
-                    let physical_size = egui::Vec2::new(width as f32, height as f32);
-                    shader_renderer.lock().paint(gl, time, physical_size, &uniforms);
-                    
-                    // === PASS 2: Render post-process to screen ===
-                    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-                    // Use viewport from callback info (this positions it correctly)
-                    gl.viewport(vp_x as i32, vp_y as i32, vp_width as i32, vp_height as i32);
-                    
-                    post_renderer.lock().paint(gl, time, physical_size, &post_uniforms);
-                }
+        self.canvas_resolution = egui::Vec2::new(frame_width as f32, frame_height as f32);
+        
+        let (width, height) = if self.fixed_resolution {
+            // Exports beyond the GPU limit are tiled, the preview shows them scaled down
+            let fit = (self.max_texture_size as f32 / export_width.max(export_height) as f32).min(1.0);
+            (
+                ((export_width as f32 * fit) as u32).max(1),
+                ((export_height as f32 * fit) as u32).max(1),
+            )
+        } else {
+            (
+                ((frame_width as f32 * self.render_scale).round() as u32).max(1),
+                ((frame_height as f32 * self.render_scale).round() as u32).max(1),
+            )
+        };
+        let inputs = self.frame_inputs(egui::Vec2::new(width as f32, height as f32));
+        
+        // Anything but canvas size renders offscreen first and is scaled into the frame
+        let scaled = (width, height) != (frame_width, frame_height);
+        let mut specs = self.target_specs();
+        if scaled {
+            specs.push(TargetSpec {
+                name: PREVIEW_TARGET_NAME.to_string(),
+                feedback: false,
+                format: TargetFormat::Rgba8,
             });
+        }
+        self.pass_targets.ensure(&self.gl, &specs, width, height);
+        self.pass_targets.swap();
+        let mut plan = self.frame_plan(&self.pass_targets);
+        if self.show_hud {
+            plan.timer = PassTimer::new(&self.profiler);
+        }
+        let preview = self.pass_targets.get(PREVIEW_TARGET_NAME)
+            .filter(|_| scaled)
+            .map(|target| target.current().fbo);
+        
+        // Render target pixel under the pointer, or the one pinned with a right-click
+        let hovered = response.hover_pos()
+            .filter(|pos| frame_rect.contains(*pos))
+            .map(|pos| {
+                let [x, y] = to_canvas(pos);
+                [
+                    ((x * width as f32 / frame_width as f32) as u32).min(width - 1),
+                    ((y * height as f32 / frame_height as f32) as u32).min(height - 1),
+                ]
+            });
+        if self.inspector.enabled && response.hovered() && ui.input(|i| i.pointer.secondary_clicked()) {
+            self.inspector.pinned = match self.inspector.pinned {
+                Some(_) => None,
+                None => hovered,
+            };
+        }
+        let inspected = self.inspector.pinned
+            .or(hovered)
+            .filter(|_| self.inspector.enabled)
+            .map(|[x, y]| [x.min(width - 1), y.min(height - 1)]);
+        let inspect = inspected.map(|pixel| PixelRequest {
+            pixel,
+            size: [width, height],
+            loupe_stage: self.inspector.loupe_stage.clone(),
+        });
+        if inspect.is_none() {
+            *self.inspector.readback.lock() = None;
+        }
+        let readback = self.inspector.readback.clone();
+        
+        let cb = egui_glow::CallbackFn::new(move |info, painter| {
+            use glow::HasContext as _;
+            let gl = painter.gl();
+            
+            // Get proper viewport from callback info
+            let viewport = info.viewport_in_pixels();
+            let viewport = [
+                viewport.left_px as i32 + frame_offset[0],
+                viewport.from_bottom_px as i32 + frame_offset[1],
+                frame_width as i32,
+                frame_height as i32,
+            ];
             
-            let callback = egui::PaintCallback {
-                rect,
-                callback: Arc::new(cb),
+            // Stages drawn straight to the destination are read back from there
+            let read_pixel = |destination: Option<glow::Framebuffer>, origin: [i32; 2]| {
+                let Some(request) = &inspect else {
+                    return;
+                };
+                let sources: Vec<ReadSource> = plan.stage_framebuffers()
+                    .into_iter()
+                    .map(|(stage, fbo)| match fbo {
+                        Some(fbo) => ReadSource { stage, fbo: Some(fbo), origin: [0, 0] },
+                        None => ReadSource { stage, fbo: destination, origin },
+                    })
+                    .collect();
+                *readback.lock() = Some(request.read(gl, &sources));
             };
-            ui.painter().add(callback);
-        } else {
-            // Single-pass rendering
-            let shader_renderer = self.shader_renderer.clone();
-            let uniforms = self.uniforms.clone();
-
-            let cb = egui_glow::CallbackFn::new(move |_info, painter| {
-                shader_renderer.lock().paint(painter.gl(), time, rect.size(), &uniforms);
-            });
-
-            let callback = egui::PaintCallback {
-                rect,
-                callback: Arc::new(cb),
+            
+            let Some(preview) = preview else {
+                plan.render(gl, &inputs, None, viewport);
+                read_pixel(None, [viewport[0], viewport[1]]);
+                return;
             };
-            ui.painter().add(callback);
+            
+            let [x, y, w, h] = viewport;
+            unsafe {
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(preview));
+                gl.viewport(0, 0, width as i32, height as i32);
+                gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+            }
+            plan.render(gl, &inputs, Some(preview), [0, 0, width as i32, height as i32]);
+            unsafe {
+                // The clear only reaches the canvas, egui keeps the scissor test on its clip rect
+                gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+                gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(preview));
+                gl.blit_framebuffer(
+                    0, 0, width as i32, height as i32,
+                    x, y, x + w, y + h,
+                    glow::COLOR_BUFFER_BIT, glow::LINEAR,
+                );
+                gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                gl.viewport(x, y, w, h);
+            }
+            read_pixel(Some(preview), [0, 0]);
+        });
+        
+        let callback = egui::PaintCallback {
+            rect,
+            callback: Arc::new(cb),
+        };
+        ui.painter().add(callback);
+        
+        // Advance after the frame is built, so the first frame after start or Reset is frame 0 at time 0, as in exports
+        if self.auto_time {
+            self.time += dt;
+        }
+        self.frame_count = self.frame_count.wrapping_add(1);
+        
+        if self.show_hud {
+            self.profiler.lock().paint_hud(ui.painter(), frame_rect);
+        }
+        if let Some([x, y]) = inspected {
+            let anchor = frame_rect.left_bottom() + egui::vec2(
+                (x as f32 + 0.5) / width as f32 * frame_rect.width(),
+                -(y as f32 + 0.5) / height as f32 * frame_rect.height(),
+            );
+            self.inspector.paint_loupe(ui.painter(), anchor, rect);
         }
     }
 }
 
+/// Largest rect with the given aspect ratio, centred in `rect`
+fn letterbox(rect: egui::Rect, aspect: f32) -> egui::Rect {
+    if rect.height() <= 0.0 || !aspect.is_finite() {
+        return rect;
+    }
+    let size = if rect.width() / rect.height() > aspect {
+        egui::vec2(rect.height() * aspect, rect.height())
+    } else {
+        egui::vec2(rect.width(), rect.width() / aspect)
+    };
+    egui::Rect::from_center_size(rect.center(), size)
+}
+
 /// Get the default shader path relative to the executable
 pub fn get_default_shader_path(path: &str) -> PathBuf {
     if let Ok(exe_path) = std::env::current_exe() {
@@ -479,6 +683,164 @@ pub fn get_default_shader_path(path: &str) -> PathBuf {
     PathBuf::from(path)
 }
 
+/// Current UTC date as Shadertoy's iDate: year, month (0-based), day, seconds since midnight
+pub fn current_date() -> [f32; 4] {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let days = (since_epoch.as_secs() / 86_400) as i64;
+    let seconds = (since_epoch.as_secs_f64() % 86_400.0) as f32;
+
+    let [year, month, day] = civil_from_days(days);
+    [year as f32, (month - 1) as f32, day as f32, seconds]
+}
+
+/// `date` moved forward by `seconds`, rolling whole days over into day, month and year
+pub fn advance_date(date: [f32; 4], seconds: f32) -> [f32; 4] {
+    let [year, month, day, start] = date;
+    let total = start as f64 + seconds as f64;
+    let extra_days = (total / 86_400.0).floor();
+    let days = days_from_civil(year as i64, month as i64 + 1, day as i64) + extra_days as i64;
+
+    let [year, month, day] = civil_from_days(days);
+    [year as f32, (month - 1) as f32, day as f32, (total - extra_days * 86_400.0) as f32]
+}
+
+/// Year, month (1-based) and day from days since 1970-01-01 (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> [i64; 3] {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    [year, month, day]
+}
+
+/// Days since 1970-01-01 of a civil date, month 1-based; the inverse of `civil_from_days`
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Apply source transformations (Shadertoy wrapping) before compiling
+pub fn prepare_shader_source(source: String, shadertoy_mode: bool) -> String {
+    if shadertoy_mode
+        && let Some(wrapped) = glsl::wrap_shadertoy(&source)
+    {
+        return wrapped;
+    }
+    source
+}
+
+/// Reconcile parsed declarations with the uniforms the driver reports for the linked program.
+/// The driver decides which uniforms exist and their types; declarations contribute annotations,
+/// lines and order. Declared uniforms the driver dropped stay listed, flagged inactive.
+pub fn reconcile_uniforms(
+    shader_name: &str,
+    mut uniforms: HashMap<String, UniformInfo>,
+    renderer: &ShaderRenderer,
+) -> HashMap<String, UniformInfo> {
+    let active_uniforms = renderer.active_uniforms();
+    
+    let mut unused: Vec<&str> = Vec::new();
+    for (name, info) in uniforms.iter_mut() {
+        info.active = active_uniforms.contains_key(name);
+        if !info.active && !info.injected && !is_builtin_uniform(name) {
+            unused.push(name);
+        }
+    }
+    if !unused.is_empty() {
+        unused.sort_unstable();
+        log::warn!("{}: uniforms optimised away by the driver: {}", shader_name, unused.join(", "));
+    }
+    
+    // Uniforms hidden from the parser by macros, includes or unusual formatting go last, by name
+    let mut reflected: Vec<(&String, UniformType)> = active_uniforms.iter()
+        .filter(|(name, _)| !is_builtin_uniform(name))
+        .filter_map(|(name, active)| match active.uniform_type() {
+            Some(uniform_type) => Some((name, uniform_type)),
+            None => {
+                if !uniforms.contains_key(name) {
+                    log::warn!("{}: {} has a type the editor doesn't support", shader_name, name);
+                }
+                None
+            }
+        })
+        .collect();
+    reflected.sort_by(|a, b| a.0.cmp(b.0));
+    
+    let mut next_order = uniforms.values().map(|info| info.order + 1).max().unwrap_or(0);
+    let mut discovered = Vec::new();
+    for (name, uniform_type) in reflected {
+        match uniforms.get_mut(name) {
+            Some(info) if info.uniform_type == uniform_type => {}
+            // The driver reports an array's size as its highest used index + 1,
+            // and some drivers report an array of which only `x[0]` is used as a plain `x`
+            Some(info) if match (&info.uniform_type, &uniform_type) {
+                (UniformType::Array(declared, _), UniformType::Array(reported, _)) => declared == reported,
+                (UniformType::Array(declared, _), reported) => **declared == *reported,
+                _ => false,
+            } => {}
+            Some(info) => {
+                log::warn!("{}: {} is declared as {:?} but the driver reports {:?}", shader_name, name, info.uniform_type, uniform_type);
+                let mut replacement = UniformInfo::new(uniform_type);
+                replacement.line = info.line;
+                replacement.order = info.order;
+                replacement.meta = info.meta.clone();
+                replacement.injected = info.injected;
+                apply_name_defaults(name, &mut replacement);
+                *info = replacement;
+            }
+            None => {
+                let info = UniformInfo {
+                    order: next_order,
+                    ..new_uniform_info(name, uniform_type)
+                };
+                next_order += 1;
+                uniforms.insert(name.clone(), info);
+                discovered.push(name.as_str());
+            }
+        }
+    }
+    if !discovered.is_empty() {
+        log::info!("{}: uniforms found by the driver but not in the source: {}", shader_name, discovered.join(", "));
+        assign_prefix_groups(&mut uniforms);
+    }
+    
+    uniforms
+}
+
+/// Keep edited values of uniforms that survive a reload with the same type
+pub fn merge_uniform_state(
+    previous: &HashMap<String, UniformInfo>,
+    new_uniforms: HashMap<String, UniformInfo>,
+) -> HashMap<String, UniformInfo> {
+    let mut merged = HashMap::new();
+    
+    for (name, new_info) in new_uniforms {
+        if let Some(old_info) = previous.get(&name)
+            && old_info.uniform_type == new_info.uniform_type
+        {
+            // Keep the edited value but refresh the declaration metadata
+            let mut info = new_info;
+            info.inherit_state(old_info);
+            merged.insert(name, info);
+            continue;
+        }
+        merged.insert(name, new_info);
+    }
+    
+    merged
+}
+
 /// Check if FFmpeg is available on the system
 pub fn is_ffmpeg_available() -> bool {
     Command::new("ffmpeg")
@@ -490,37 +852,64 @@ pub fn is_ffmpeg_available() -> bool {
 
 /// Parse GLSL shader source to detect uniform declarations
 pub fn parse_uniforms(shader_source: &str) -> HashMap<String, UniformInfo> {
-    use regex::Regex;
-    
     let mut uniforms = HashMap::new();
     
-    let re = Regex::new(
-        r"uniform\s+(float|vec2|vec3|vec4|sampler2D)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*;"
-    ).expect("Invalid regex pattern");
-    
-    for cap in re.captures_iter(shader_source) {
-        let type_str = cap.get(1).map(|m| m.as_str()).unwrap_or("");
-        let name = cap.get(2).map(|m| m.as_str()).unwrap_or("");
-        
-        let uniform_type = match type_str {
-            "float" => UniformType::Float,
-            "vec2" => UniformType::Vec2,
-            "vec3" => UniformType::Vec3,
-            "vec4" => UniformType::Vec4,
-            "sampler2D" => UniformType::Sampler2D,
-            _ => continue,
+    for (order, declaration) in glsl::parse_uniform_declarations(shader_source).into_iter().enumerate() {
+        let Some(element_type) = UniformType::from_glsl(&declaration.type_name) else {
+            continue;
+        };
+        let uniform_type = match declaration.array_len {
+            Some(len) => UniformType::Array(Box::new(element_type), len),
+            None => element_type,
         };
         
-        let value = UniformValue::default_for_type(&uniform_type);
-        
-        uniforms.insert(
-            name.to_string(),
-            UniformInfo {
-                uniform_type,
-                value,
-            }
-        );
+        let mut info = UniformInfo::new(uniform_type);
+        info.line = declaration.line;
+        info.order = order;
+        info.meta = UniformMeta::from_annotations(&declaration.annotations);
+        info.meta.group = declaration.group;
+        info.injected = declaration.injected;
+        apply_name_defaults(&declaration.name, &mut info);
+        uniforms.insert(declaration.name, info);
     }
     
+    assign_prefix_groups(&mut uniforms);
     uniforms
-}
\ No newline at end of file
+}
+
+/// A uniform known only by name and type, e.g. from driver reflection
+fn new_uniform_info(name: &str, uniform_type: UniformType) -> UniformInfo {
+    let mut info = UniformInfo::new(uniform_type);
+    apply_name_defaults(name, &mut info);
+    info
+}
+
+/// Color pickers, sampler inputs and the default value implied by the name and annotations
+fn apply_name_defaults(name: &str, info: &mut UniformInfo) {
+    if info.meta.color.is_none()
+        && info.uniform_type.is_color_compatible()
+        && looks_like_color(name)
+    {
+        info.meta.color = Some(ColorSpace::Srgb);
+    }
+    info.color_space = info.meta.color.unwrap_or_default();
+    if info.uniform_type == UniformType::Sampler2D {
+        info.input = info.meta.input.clone().or_else(|| render_graph::default_input(name));
+    }
+    info.value = info.default_value();
+}
+
+/// Uniforms without an explicit group share one when two or more have the same name prefix
+fn assign_prefix_groups(uniforms: &mut HashMap<String, UniformInfo>) {
+    let mut prefix_counts: HashMap<String, usize> = HashMap::new();
+    for (name, info) in uniforms.iter() {
+        if info.meta.group.is_none() && let Some(prefix) = name_prefix_group(name) {
+            *prefix_counts.entry(prefix).or_default() += 1;
+        }
+    }
+    for (name, info) in uniforms.iter_mut() {
+        if info.meta.group.is_none() {
+            info.meta.group = name_prefix_group(name).filter(|prefix| prefix_counts[prefix] > 1);
+        }
+    }
+}
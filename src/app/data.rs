@@ -1,9 +1,196 @@
+use super::glsl::{Annotation, AnnotationArg};
+use std::borrow::Cow;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct UniformInfo {
     pub uniform_type: UniformType,
     pub value: UniformValue,
+    /// Source line of the declaration (1-based, 0 if unknown)
+    pub line: usize,
+    /// Position in declaration order
+    pub order: usize,
+    pub meta: UniformMeta,
+    /// Space color values are uploaded in (values are edited as sRGB)
+    pub color_space: ColorSpace,
+    /// vec2 value follows pointer drags on the preview canvas
+    pub canvas_drag: bool,
+    /// Pass whose output a sampler reads instead of a texture file
+    pub input: Option<String>,
+    /// How the sampler reads the output of `input`; a texture file keeps its settings in its handle
+    pub input_params: TextureParams,
+    /// Used by the linked program; false when the driver optimised it away
+    pub active: bool,
+    /// Declared by the Shadertoy wrapper, so being unused is expected
+    pub injected: bool,
+}
+
+impl UniformInfo {
+    pub fn new(uniform_type: UniformType) -> Self {
+        let value = UniformValue::default_for_type(&uniform_type);
+        Self {
+            uniform_type,
+            value,
+            line: 0,
+            order: 0,
+            meta: UniformMeta::default(),
+            color_space: ColorSpace::default(),
+            canvas_drag: false,
+            input: None,
+            input_params: TextureParams::PASS_OUTPUT,
+            active: true,
+            injected: false,
+        }
+    }
+
+    /// Carry user-edited state over from a previous declaration of the same uniform
+    pub fn inherit_state(&mut self, previous: &UniformInfo) {
+        self.value = previous.value.clone();
+        self.color_space = previous.color_space;
+        self.canvas_drag = previous.canvas_drag;
+        // A changed `@input` annotation wins over the source picked in the UI
+        if self.meta.input == previous.meta.input {
+            self.input = previous.input.clone();
+        }
+        self.input_params = previous.input_params;
+    }
+
+    /// Value as it should be sent to the GPU
+    pub fn upload_value(&self) -> Cow<'_, UniformValue> {
+        if self.meta.color.is_some() && self.color_space == ColorSpace::Linear {
+            Cow::Owned(self.value.srgb_to_linear())
+        } else {
+            Cow::Borrowed(&self.value)
+        }
+    }
+
+    /// Store a color read back from a render target so it uploads as exactly these values
+    pub fn set_color(&mut self, rgba: [f32; 4]) {
+        use egui::ecolor::gamma_from_linear;
+        let [mut r, mut g, mut b, a] = rgba;
+        if self.meta.color.is_some() && self.color_space == ColorSpace::Linear {
+            [r, g, b] = [gamma_from_linear(r), gamma_from_linear(g), gamma_from_linear(b)];
+        }
+        match &mut self.value {
+            UniformValue::Vec3(vals) => *vals = [r, g, b],
+            UniformValue::Vec4(vals) => *vals = [r, g, b, a],
+            _ => {}
+        }
+    }
+
+    /// Default value, taking a `@default` annotation into account
+    pub fn default_value(&self) -> UniformValue {
+        let mut value = UniformValue::default_for_type(&self.uniform_type);
+        if let Some(components) = &self.meta.default {
+            value.set_components(components);
+        }
+        value
+    }
+
+    /// Name shown in the UI
+    pub fn display_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.meta.label.as_deref().unwrap_or(name)
+    }
+}
+
+/// Editor metadata declared with comment annotations, e.g.
+/// `uniform float uScale; // @range(0, 50) @default(10) @step(0.5) @label("Scale")`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UniformMeta {
+    pub range: Option<(f64, f64)>,
+    /// Separate y bounds for vec2 pads, from `@range(xmin, xmax, ymin, ymax)`
+    pub range_y: Option<(f64, f64)>,
+    pub default: Option<Vec<f64>>,
+    pub step: Option<f64>,
+    pub label: Option<String>,
+    /// Show a color picker; the space is the initial upload space
+    pub color: Option<ColorSpace>,
+    /// Named choices for int/uint uniforms; the value is the selected index
+    pub options: Option<Vec<String>>,
+    /// Collapsible group from `@group("Name")` markers or a shared name prefix
+    pub group: Option<String>,
+    /// Pass output bound to a sampler, from `@input("Buffer A")`
+    pub input: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl UniformMeta {
+    pub fn from_annotations(annotations: &[Annotation]) -> Self {
+        let mut meta = Self::default();
+
+        for annotation in annotations {
+            match annotation.name.as_str() {
+                "range" => match annotation.numbers()[..] {
+                    [x_min, x_max, y_min, y_max, ..] => {
+                        meta.range = Some((x_min, x_max));
+                        meta.range_y = Some((y_min, y_max));
+                    }
+                    [min, max, ..] => meta.range = Some((min, max)),
+                    _ => {}
+                },
+                "default" => {
+                    let numbers = annotation.numbers();
+                    if !numbers.is_empty() {
+                        meta.default = Some(numbers);
+                    }
+                }
+                "step" => meta.step = annotation.numbers().first().copied(),
+                "label" => meta.label = annotation.first_str().map(str::to_string),
+                "options" => {
+                    let options: Vec<String> = annotation.args.iter()
+                        .map(|arg| match arg {
+                            AnnotationArg::Number(n) => n.to_string(),
+                            AnnotationArg::String(s) | AnnotationArg::Ident(s) => s.clone(),
+                        })
+                        .collect();
+                    if !options.is_empty() {
+                        meta.options = Some(options);
+                    }
+                }
+                "color" => {
+                    meta.color = match annotation.first_str() {
+                        Some(space) if space.eq_ignore_ascii_case("linear") => Some(ColorSpace::Linear),
+                        _ => Some(ColorSpace::Srgb),
+                    };
+                }
+                "input" => meta.input = annotation.first_str().map(str::to_string),
+                // Groups are resolved by the parser from preceding markers
+                "group" => {}
+                other => log::warn!("Unknown uniform annotation: @{}", other),
+            }
+        }
+
+        meta
+    }
+
+    pub fn range_f32(&self) -> std::ops::RangeInclusive<f32> {
+        let (min, max) = self.range.unwrap_or((0.0, 1.0));
+        min as f32..=max as f32
+    }
+
+    pub fn range_y_f32(&self) -> std::ops::RangeInclusive<f32> {
+        match self.range_y {
+            Some((min, max)) => min as f32..=max as f32,
+            None => self.range_f32(),
+        }
+    }
+
+    /// Map a normalized 0..1 position into the vec2 bounds
+    pub fn denormalize_xy(&self, uv: egui::Vec2) -> [f32; 2] {
+        let x = self.range_f32();
+        let y = self.range_y_f32();
+        [
+            egui::lerp(x, uv.x.clamp(0.0, 1.0)),
+            egui::lerp(y, uv.y.clamp(0.0, 1.0)),
+        ]
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,7 +199,92 @@ pub enum UniformType {
     Vec2,
     Vec3,
     Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    BVec2,
+    BVec3,
+    BVec4,
+    Mat2,
+    Mat3,
+    Mat4,
     Sampler2D,
+    Array(Box<UniformType>, usize),
+}
+
+impl UniformType {
+    /// vec3/vec4 values (or arrays of them) that can be edited as colors
+    pub fn is_color_compatible(&self) -> bool {
+        match self {
+            UniformType::Vec3 | UniformType::Vec4 => true,
+            UniformType::Array(element_type, _) => element_type.is_color_compatible(),
+            _ => false,
+        }
+    }
+
+    /// Map a GLSL type name to a supported uniform type
+    pub fn from_glsl(type_name: &str) -> Option<Self> {
+        Some(match type_name {
+            "float" => UniformType::Float,
+            "vec2" => UniformType::Vec2,
+            "vec3" => UniformType::Vec3,
+            "vec4" => UniformType::Vec4,
+            "int" => UniformType::Int,
+            "ivec2" => UniformType::IVec2,
+            "ivec3" => UniformType::IVec3,
+            "ivec4" => UniformType::IVec4,
+            "uint" => UniformType::UInt,
+            "uvec2" => UniformType::UVec2,
+            "uvec3" => UniformType::UVec3,
+            "uvec4" => UniformType::UVec4,
+            "bool" => UniformType::Bool,
+            "bvec2" => UniformType::BVec2,
+            "bvec3" => UniformType::BVec3,
+            "bvec4" => UniformType::BVec4,
+            "mat2" | "mat2x2" => UniformType::Mat2,
+            "mat3" | "mat3x3" => UniformType::Mat3,
+            "mat4" | "mat4x4" => UniformType::Mat4,
+            "sampler2D" => UniformType::Sampler2D,
+            _ => return None,
+        })
+    }
+
+    /// Map a type reported by `get_active_uniform`, with the reported size of arrays
+    pub fn from_gl(gl_type: u32, array_size: Option<usize>) -> Option<Self> {
+        let element_type = match gl_type {
+            glow::FLOAT => UniformType::Float,
+            glow::FLOAT_VEC2 => UniformType::Vec2,
+            glow::FLOAT_VEC3 => UniformType::Vec3,
+            glow::FLOAT_VEC4 => UniformType::Vec4,
+            glow::INT => UniformType::Int,
+            glow::INT_VEC2 => UniformType::IVec2,
+            glow::INT_VEC3 => UniformType::IVec3,
+            glow::INT_VEC4 => UniformType::IVec4,
+            glow::UNSIGNED_INT => UniformType::UInt,
+            glow::UNSIGNED_INT_VEC2 => UniformType::UVec2,
+            glow::UNSIGNED_INT_VEC3 => UniformType::UVec3,
+            glow::UNSIGNED_INT_VEC4 => UniformType::UVec4,
+            glow::BOOL => UniformType::Bool,
+            glow::BOOL_VEC2 => UniformType::BVec2,
+            glow::BOOL_VEC3 => UniformType::BVec3,
+            glow::BOOL_VEC4 => UniformType::BVec4,
+            glow::FLOAT_MAT2 => UniformType::Mat2,
+            glow::FLOAT_MAT3 => UniformType::Mat3,
+            glow::FLOAT_MAT4 => UniformType::Mat4,
+            glow::SAMPLER_2D => UniformType::Sampler2D,
+            _ => return None,
+        };
+        Some(match array_size {
+            Some(size) => UniformType::Array(Box::new(element_type), size),
+            None => element_type,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -21,7 +293,24 @@ pub enum UniformValue {
     Vec2([f32; 2]),
     Vec3([f32; 3]),
     Vec4([f32; 4]),
+    Int(i32),
+    IVec2([i32; 2]),
+    IVec3([i32; 3]),
+    IVec4([i32; 4]),
+    UInt(u32),
+    UVec2([u32; 2]),
+    UVec3([u32; 3]),
+    UVec4([u32; 4]),
+    Bool(bool),
+    BVec2([bool; 2]),
+    BVec3([bool; 3]),
+    BVec4([bool; 4]),
+    /// Matrices are stored column-major, matching GLSL
+    Mat2([f32; 4]),
+    Mat3([f32; 9]),
+    Mat4([f32; 16]),
     Sampler2D(Option<TextureHandle>), 
+    Array(Vec<UniformValue>),
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +319,108 @@ pub struct TextureHandle {
     pub texture_id: Option<glow::Texture>,
     pub width: u32,
     pub height: u32,
+    /// Sampling settings, edited per sampler
+    pub params: TextureParams,
+    /// Decoded image of a texture file, kept so flip and sRGB changes re-upload without reading the file again
+    pub image: Option<Arc<image::RgbaImage>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl TextureWrap {
+    pub const ALL: [TextureWrap; 3] = [TextureWrap::Repeat, TextureWrap::Clamp, TextureWrap::Mirror];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TextureWrap::Repeat => "Repeat",
+            TextureWrap::Clamp => "Clamp",
+            TextureWrap::Mirror => "Mirror",
+        }
+    }
+
+    pub fn gl_wrap(self) -> u32 {
+        match self {
+            TextureWrap::Repeat => glow::REPEAT,
+            TextureWrap::Clamp => glow::CLAMP_TO_EDGE,
+            TextureWrap::Mirror => glow::MIRRORED_REPEAT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+impl TextureFilter {
+    pub const ALL: [TextureFilter; 2] = [TextureFilter::Linear, TextureFilter::Nearest];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TextureFilter::Linear => "Linear",
+            TextureFilter::Nearest => "Nearest",
+        }
+    }
+
+    /// Minification filter, blending between mip levels the same way as within one
+    pub fn gl_min_filter(self, mipmaps: bool) -> u32 {
+        match (self, mipmaps) {
+            (TextureFilter::Linear, false) => glow::LINEAR,
+            (TextureFilter::Linear, true) => glow::LINEAR_MIPMAP_LINEAR,
+            (TextureFilter::Nearest, false) => glow::NEAREST,
+            (TextureFilter::Nearest, true) => glow::NEAREST_MIPMAP_NEAREST,
+        }
+    }
+
+    pub fn gl_mag_filter(self) -> u32 {
+        match self {
+            TextureFilter::Linear => glow::LINEAR,
+            TextureFilter::Nearest => glow::NEAREST,
+        }
+    }
+}
+
+/// How a sampler reads its texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureParams {
+    pub wrap: TextureWrap,
+    pub filter: TextureFilter,
+    pub mipmaps: bool,
+    /// Upload bottom row first, so v = 0 is the bottom of the image
+    pub flip_y: bool,
+    /// Store as sRGB so sampling returns linear values
+    pub srgb: bool,
+}
+
+impl Default for TextureParams {
+    fn default() -> Self {
+        Self {
+            wrap: TextureWrap::Repeat,
+            filter: TextureFilter::Linear,
+            mipmaps: false,
+            flip_y: true,
+            srgb: false,
+        }
+    }
+}
+
+impl TextureParams {
+    /// Pass outputs clamp at the edges and have no mipmaps
+    pub const PASS_OUTPUT: Self = Self {
+        wrap: TextureWrap::Clamp,
+        filter: TextureFilter::Linear,
+        mipmaps: false,
+        flip_y: false,
+        srgb: false,
+    };
 }
 
 impl UniformValue {
@@ -39,14 +430,446 @@ impl UniformValue {
             UniformType::Vec2 => UniformValue::Vec2([0.5, 0.5]),
             UniformType::Vec3 => UniformValue::Vec3([0.5, 0.5, 0.5]),
             UniformType::Vec4 => UniformValue::Vec4([1.0, 1.0, 1.0, 1.0]),
+            UniformType::Int => UniformValue::Int(0),
+            UniformType::IVec2 => UniformValue::IVec2([0; 2]),
+            UniformType::IVec3 => UniformValue::IVec3([0; 3]),
+            UniformType::IVec4 => UniformValue::IVec4([0; 4]),
+            UniformType::UInt => UniformValue::UInt(0),
+            UniformType::UVec2 => UniformValue::UVec2([0; 2]),
+            UniformType::UVec3 => UniformValue::UVec3([0; 3]),
+            UniformType::UVec4 => UniformValue::UVec4([0; 4]),
+            UniformType::Bool => UniformValue::Bool(false),
+            UniformType::BVec2 => UniformValue::BVec2([false; 2]),
+            UniformType::BVec3 => UniformValue::BVec3([false; 3]),
+            UniformType::BVec4 => UniformValue::BVec4([false; 4]),
+            UniformType::Mat2 => UniformValue::Mat2(identity_matrix()),
+            UniformType::Mat3 => UniformValue::Mat3(identity_matrix()),
+            UniformType::Mat4 => UniformValue::Mat4(identity_matrix()),
             UniformType::Sampler2D => UniformValue::Sampler2D(None),
+            UniformType::Array(element_type, len) => {
+                UniformValue::Array(vec![Self::default_for_type(element_type); *len])
+            }
+        }
+    }
+
+    /// Overwrite numeric components; a single component is applied to all of them
+    pub fn set_components(&mut self, components: &[f64]) {
+        fn fill<T: Copy>(vals: &mut [T], components: &[f64], convert: impl Fn(f64) -> T) {
+            for (i, val) in vals.iter_mut().enumerate() {
+                let component = if components.len() == 1 { components.first() } else { components.get(i) };
+                if let Some(c) = component {
+                    *val = convert(*c);
+                }
+            }
+        }
+
+        let to_f32 = |c: f64| c as f32;
+        let to_i32 = |c: f64| c.round() as i32;
+        let to_u32 = |c: f64| c.round().max(0.0) as u32;
+        let to_bool = |c: f64| c != 0.0;
+
+        match self {
+            UniformValue::Float(val) => fill(std::slice::from_mut(val), components, to_f32),
+            UniformValue::Vec2(vals) => fill(vals, components, to_f32),
+            UniformValue::Vec3(vals) => fill(vals, components, to_f32),
+            UniformValue::Vec4(vals) => fill(vals, components, to_f32),
+            UniformValue::Int(val) => fill(std::slice::from_mut(val), components, to_i32),
+            UniformValue::IVec2(vals) => fill(vals, components, to_i32),
+            UniformValue::IVec3(vals) => fill(vals, components, to_i32),
+            UniformValue::IVec4(vals) => fill(vals, components, to_i32),
+            UniformValue::UInt(val) => fill(std::slice::from_mut(val), components, to_u32),
+            UniformValue::UVec2(vals) => fill(vals, components, to_u32),
+            UniformValue::UVec3(vals) => fill(vals, components, to_u32),
+            UniformValue::UVec4(vals) => fill(vals, components, to_u32),
+            UniformValue::Bool(val) => fill(std::slice::from_mut(val), components, to_bool),
+            UniformValue::BVec2(vals) => fill(vals, components, to_bool),
+            UniformValue::BVec3(vals) => fill(vals, components, to_bool),
+            UniformValue::BVec4(vals) => fill(vals, components, to_bool),
+            // A full matrix overrides everything, a single value scales the identity
+            UniformValue::Mat2(vals) => set_matrix(vals, components),
+            UniformValue::Mat3(vals) => set_matrix(vals, components),
+            UniformValue::Mat4(vals) => set_matrix(vals, components),
+            UniformValue::Sampler2D(_) => {}
+            UniformValue::Array(elements) => {
+                for element in elements {
+                    element.set_components(components);
+                }
+            }
+        }
+    }
+
+    /// Convert sRGB-encoded color components to linear, leaving alpha untouched
+    pub fn srgb_to_linear(&self) -> Self {
+        use egui::ecolor::linear_from_gamma;
+        match self {
+            UniformValue::Vec3([r, g, b]) => {
+                UniformValue::Vec3([linear_from_gamma(*r), linear_from_gamma(*g), linear_from_gamma(*b)])
+            }
+            UniformValue::Vec4([r, g, b, a]) => {
+                UniformValue::Vec4([linear_from_gamma(*r), linear_from_gamma(*g), linear_from_gamma(*b), *a])
+            }
+            UniformValue::Array(elements) => {
+                UniformValue::Array(elements.iter().map(Self::srgb_to_linear).collect())
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Float components of float vectors and matrices
+    pub fn float_components(&self) -> &[f32] {
+        match self {
+            UniformValue::Float(val) => std::slice::from_ref(val),
+            UniformValue::Vec2(vals) => vals,
+            UniformValue::Vec3(vals) => vals,
+            UniformValue::Vec4(vals) => vals,
+            UniformValue::Mat2(vals) => vals,
+            UniformValue::Mat3(vals) => vals,
+            UniformValue::Mat4(vals) => vals,
+            _ => &[],
+        }
+    }
+
+    /// Components of signed integer and boolean values as GL ints
+    pub fn int_components(&self) -> Vec<i32> {
+        match self {
+            UniformValue::Int(val) => vec![*val],
+            UniformValue::IVec2(vals) => vals.to_vec(),
+            UniformValue::IVec3(vals) => vals.to_vec(),
+            UniformValue::IVec4(vals) => vals.to_vec(),
+            UniformValue::Bool(val) => vec![*val as i32],
+            UniformValue::BVec2(vals) => vals.iter().map(|b| *b as i32).collect(),
+            UniformValue::BVec3(vals) => vals.iter().map(|b| *b as i32).collect(),
+            UniformValue::BVec4(vals) => vals.iter().map(|b| *b as i32).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Components of unsigned integer values
+    pub fn uint_components(&self) -> &[u32] {
+        match self {
+            UniformValue::UInt(val) => std::slice::from_ref(val),
+            UniformValue::UVec2(vals) => vals,
+            UniformValue::UVec3(vals) => vals,
+            UniformValue::UVec4(vals) => vals,
+            _ => &[],
+        }
+    }
+}
+
+fn set_matrix<const L: usize>(vals: &mut [f32; L], components: &[f64]) {
+    if let [scale] = components {
+        *vals = identity_matrix();
+        vals.iter_mut().for_each(|v| *v *= *scale as f32);
+    } else {
+        for (val, c) in vals.iter_mut().zip(components) {
+            *val = *c as f32;
         }
     }
 }
 
+/// Group name from a naming convention: `light_color` -> "light", `uLightColor` -> "Light"
+pub fn name_prefix_group(name: &str) -> Option<String> {
+    let name = name.strip_prefix("u_").unwrap_or(name);
+
+    if let Some((prefix, rest)) = name.split_once('_') {
+        return (!prefix.is_empty() && !rest.is_empty()).then(|| prefix.to_string());
+    }
+
+    // camelCase with a `u` prefix: take the first capitalized word
+    let rest = name.strip_prefix('u')?;
+    let mut chars = rest.char_indices();
+    let (_, first) = chars.next()?;
+    if !first.is_ascii_uppercase() {
+        return None;
+    }
+    let end = chars
+        .find(|(_, c)| !c.is_ascii_lowercase())
+        .map_or(rest.len(), |(i, _)| i);
+    (end < rest.len()).then(|| rest[..end].to_string())
+}
+
+/// Whether a uniform name suggests it holds a color
+pub fn looks_like_color(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.contains("color") || name.contains("colour")
+}
+
+/// Column-major N x N identity matrix
+pub fn identity_matrix<const L: usize>() -> [f32; L] {
+    let n = (L as f32).sqrt() as usize;
+    let mut m = [0.0; L];
+    for i in 0..n {
+        m[i * n + i] = 1.0;
+    }
+    m
+}
+
+/// Translate/rotate/scale parameters used to compose transform matrices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransformParams {
+    pub translate: [f32; 3],
+    /// Euler angles in degrees, applied in X, Y, Z order
+    pub rotate: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Default for TransformParams {
+    fn default() -> Self {
+        Self {
+            translate: [0.0; 3],
+            rotate: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl TransformParams {
+    /// 2D homogeneous transform (T * R * S), rotating around Z
+    pub fn to_mat3(self) -> [f32; 9] {
+        let (s, c) = self.rotate[2].to_radians().sin_cos();
+        let [sx, sy, _] = self.scale;
+        let [tx, ty, _] = self.translate;
+        [
+            sx * c, sx * s, 0.0,
+            -sy * s, sy * c, 0.0,
+            tx, ty, 1.0,
+        ]
+    }
+
+    /// 3D affine transform (T * Rz * Ry * Rx * S)
+    pub fn to_mat4(self) -> [f32; 16] {
+        let (sx, cx) = self.rotate[0].to_radians().sin_cos();
+        let (sy, cy) = self.rotate[1].to_radians().sin_cos();
+        let (sz, cz) = self.rotate[2].to_radians().sin_cos();
+        let rotation = [
+            [cz * cy, cz * sy * sx - sz * cx, cz * sy * cx + sz * sx],
+            [sz * cy, sz * sy * sx + cz * cx, sz * sy * cx - cz * sx],
+            [-sy, cy * sx, cy * cx],
+        ];
+
+        let mut m = identity_matrix::<16>();
+        for col in 0..3 {
+            for row in 0..3 {
+                m[col * 4 + row] = rotation[row][col] * self.scale[col];
+            }
+        }
+        m[12..15].copy_from_slice(&self.translate);
+        m
+    }
+}
+
+/// Uniforms set by the renderer every frame rather than edited in the UI
+pub const BUILTIN_UNIFORMS: &[&str] = &[
+    "u_time",
+    "u_resolution",
+    "u_mouse",
+    "u_frame",
+    "u_timeDelta",
+    "u_date",
+    "u_fps",
+    "u_tileOffset",
+    // Set by the vertex shader to map `v_uv` onto tiles
+    "u_uvRect",
+    // Shadertoy compatibility
+    "iResolution",
+    "iTime",
+    "iTimeDelta",
+    "iFrameRate",
+    "iFrame",
+    "iMouse",
+    "iDate",
+    "iChannelResolution",
+];
+
+/// `u_date` at time zero of exports until the user picks another: 2000-01-01 00:00
+pub const DEFAULT_EXPORT_DATE: [f32; 4] = [2000.0, 0.0, 1.0, 0.0];
+
+pub fn is_builtin_uniform(name: &str) -> bool {
+    BUILTIN_UNIFORMS.contains(&name)
+}
+
+/// Per-frame values for the built-in uniforms
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameInputs {
+    pub time: f32,
+    pub time_delta: f32,
+    pub frame: u32,
+    pub frame_rate: f32,
+    /// Render target size in physical pixels
+    pub resolution: egui::Vec2,
+    pub mouse: MouseState,
+    /// Year, month (0-based), day, seconds since midnight
+    pub date: [f32; 4],
+    /// Part of the `resolution` frame being drawn: pixel offset xy, size zw. Zero size draws the whole frame
+    pub tile: [f32; 4],
+}
+
+impl FrameInputs {
+    /// Pixel size of the framebuffer being drawn: the tile, or the whole frame
+    pub fn target_size(&self) -> egui::Vec2 {
+        match self.tile {
+            [_, _, w, h] if w > 0.0 && h > 0.0 => egui::Vec2::new(w, h),
+            _ => self.resolution,
+        }
+    }
+
+    /// Range of the whole frame's UVs the drawn tile covers: offset xy, scale zw
+    pub fn uv_rect(&self) -> [f32; 4] {
+        match self.tile {
+            [x, y, w, h] if w > 0.0 && h > 0.0 => {
+                let size = self.resolution;
+                [x / size.x, y / size.y, w / size.x, h / size.y]
+            }
+            _ => [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Pointer state on the preview canvas in physical pixels, origin bottom-left
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MouseState {
+    /// Last position while a button was held
+    pub position: [f32; 2],
+    /// Last position over the canvas, pressed or not
+    pub hover: [f32; 2],
+    /// Where the last press started
+    pub click: [f32; 2],
+    pub down: bool,
+    /// The press started this frame
+    pub pressed: bool,
+}
+
+impl MouseState {
+    /// Shadertoy layout: xy = position, zw = click, z > 0 while held, w > 0 on the press frame
+    pub fn to_vec4(self) -> [f32; 4] {
+        let [z, w] = self.signed_click();
+        [self.position[0], self.position[1], z, w]
+    }
+
+    /// `u_mouse` layout: xy = hover position, zw = click with the same sign convention as `iMouse`
+    pub fn to_hover_vec4(self) -> [f32; 4] {
+        let [z, w] = self.signed_click();
+        [self.hover[0], self.hover[1], z, w]
+    }
+
+    /// Map the coordinates onto a render target of a different size
+    pub fn scaled(self, factor: egui::Vec2) -> Self {
+        let scale = |[x, y]: [f32; 2]| [x * factor.x, y * factor.y];
+        Self {
+            position: scale(self.position),
+            hover: scale(self.hover),
+            click: scale(self.click),
+            ..self
+        }
+    }
+
+    fn signed_click(self) -> [f32; 2] {
+        let z = if self.down { self.click[0] } else { -self.click[0] };
+        let w = if self.pressed { self.click[1] } else { -self.click[1] };
+        [z, w]
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ExportProgress {
     pub current_frame: u32,
     pub total_frames: u32,
     pub status: String,
-}
\ No newline at end of file
+}
+
+/// Storage of a pass's render target; float formats keep values outside 0..1 between passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TargetFormat {
+    #[default]
+    Rgba8,
+    Rgba16F,
+    Rgba32F,
+}
+
+impl TargetFormat {
+    pub const ALL: [TargetFormat; 3] = [TargetFormat::Rgba8, TargetFormat::Rgba16F, TargetFormat::Rgba32F];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TargetFormat::Rgba8 => "RGBA8",
+            TargetFormat::Rgba16F => "RGBA16F",
+            TargetFormat::Rgba32F => "RGBA32F",
+        }
+    }
+
+    /// Internal format and pixel type for `tex_image_2d`
+    pub fn gl_format(self) -> (u32, u32) {
+        match self {
+            TargetFormat::Rgba8 => (glow::RGBA8, glow::UNSIGNED_BYTE),
+            TargetFormat::Rgba16F => (glow::RGBA16F, glow::HALF_FLOAT),
+            TargetFormat::Rgba32F => (glow::RGBA32F, glow::FLOAT),
+        }
+    }
+}
+
+/// Operator mapping HDR values into 0..1 before the final image is quantised
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tonemap {
+    #[default]
+    None,
+    Reinhard,
+    Aces,
+    Filmic,
+}
+
+impl Tonemap {
+    pub const ALL: [Tonemap; 4] = [Tonemap::None, Tonemap::Reinhard, Tonemap::Aces, Tonemap::Filmic];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Tonemap::None => "None (clamp)",
+            Tonemap::Reinhard => "Reinhard",
+            Tonemap::Aces => "ACES (fitted)",
+            Tonemap::Filmic => "Filmic (Hable)",
+        }
+    }
+}
+
+/// Filter that reduces a supersampled export to its final size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DownsampleFilter {
+    Box,
+    #[default]
+    Tent,
+    Lanczos,
+}
+
+impl DownsampleFilter {
+    pub const ALL: [DownsampleFilter; 3] = [DownsampleFilter::Box, DownsampleFilter::Tent, DownsampleFilter::Lanczos];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DownsampleFilter::Box => "Box",
+            DownsampleFilter::Tent => "Tent",
+            DownsampleFilter::Lanczos => "Lanczos",
+        }
+    }
+
+    /// Output pixels on each side of a filtered pixel that its kernel reaches
+    pub fn radius(self) -> u32 {
+        match self {
+            DownsampleFilter::Box | DownsampleFilter::Tent => 1,
+            DownsampleFilter::Lanczos => 2,
+        }
+    }
+}
+
+/// How the final image is written to the screen or an export
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OutputSettings {
+    pub tonemap: Tonemap,
+    /// Exposure in stops, applied before tonemapping
+    pub exposure: f32,
+}
+
+impl OutputSettings {
+    /// Anything to do beyond clamping, which the output framebuffer does by itself
+    pub fn needs_present(&self) -> bool {
+        self.tonemap != Tonemap::None || self.exposure != 0.0
+    }
+}
@@ -1,12 +1,23 @@
-use crate::app::ShaderApp;
-use super::data::{ExportProgress, TextureHandle};
+use crate::app::{advance_date, ShaderApp};
+use super::data::{ExportProgress, FrameInputs, TargetFormat, TextureHandle, TextureParams};
+use super::render_graph::{render_downsample, PassTargets, RenderTarget, OUTPUT_TARGET_NAME};
 
 use std::path::Path;
 use std::process::Command;
+use std::sync::Arc;
 use std::io::Write;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use glow::HasContext;
 
+/// Largest side of an export tile in rendered pixels, keeping memory bounded for huge exports
+pub const EXPORT_TILE_SIZE: u32 = 4096;
+
+/// Frames an image export replays at most for feedback; longer previews replay only the last ones
+pub const MAX_FEEDBACK_REPLAY_FRAMES: u32 = 3600;
+
+/// Time spent on replayed frames per UI frame, so the window stays responsive
+const REPLAY_BUDGET: Duration = Duration::from_millis(30);
+
 // ==========================================
 // TEXTURE LOADING
 // ==========================================
@@ -22,58 +33,92 @@ pub fn load_texture_from_file(
     
     let (width, height) = img.dimensions();
     
-    // ← FIX: Flip image vertically (OpenGL expects bottom-left origin)
-    let flipped = flip_image_vertically(&img, width, height);
+    let texture = unsafe {
+        gl.create_texture()
+            .map_err(|e| format!("Failed to create texture: {}", e))?
+    };
+    
+    let handle = TextureHandle {
+        path: path.to_path_buf(),
+        texture_id: Some(texture),
+        width,
+        height,
+        params: TextureParams::default(),
+        image: Some(Arc::new(img)),
+    };
+    upload_texture_image(gl, &handle);
+    
+    Ok(handle)
+}
+
+/// Upload the kept image again after flip or sRGB changed, then apply the sampling settings
+pub fn upload_texture_image(gl: &glow::Context, handle: &TextureHandle) {
+    let (Some(texture), Some(img)) = (handle.texture_id, &handle.image) else {
+        return;
+    };
+    
+    // OpenGL expects bottom-left origin, images are top-left
+    let flipped;
+    let pixels = if handle.params.flip_y {
+        flipped = flip_image_vertically(img, handle.width, handle.height);
+        &flipped[..]
+    } else {
+        img.as_raw()
+    };
+    let internal_format = if handle.params.srgb { glow::SRGB8_ALPHA8 } else { glow::RGBA8 };
     
     unsafe {
-        let texture = gl.create_texture()
-            .map_err(|e| format!("Failed to create texture: {}", e))?;
-        
         gl.bind_texture(glow::TEXTURE_2D, Some(texture));
-        
-        // Upload flipped texture data
         gl.tex_image_2d(
             glow::TEXTURE_2D,
             0,
-            glow::RGBA as i32,
-            width as i32,
-            height as i32,
+            internal_format as i32,
+            handle.width as i32,
+            handle.height as i32,
             0,
             glow::RGBA,
             glow::UNSIGNED_BYTE,
-            Some(&flipped),  // ← Use flipped data
+            Some(pixels),
         );
-        
-        // Set texture parameters
+        gl.bind_texture(glow::TEXTURE_2D, None);
+    }
+    
+    apply_texture_params(gl, handle);
+}
+
+/// Set wrap and filter on the texture and build mipmaps if they are enabled
+pub fn apply_texture_params(gl: &glow::Context, handle: &TextureHandle) {
+    let Some(texture) = handle.texture_id else {
+        return;
+    };
+    let params = handle.params;
+    
+    unsafe {
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        if params.mipmaps {
+            gl.generate_mipmap(glow::TEXTURE_2D);
+        }
         gl.tex_parameter_i32(
             glow::TEXTURE_2D,
             glow::TEXTURE_MIN_FILTER,
-            glow::LINEAR as i32,
+            params.filter.gl_min_filter(params.mipmaps) as i32,
         );
         gl.tex_parameter_i32(
             glow::TEXTURE_2D,
             glow::TEXTURE_MAG_FILTER,
-            glow::LINEAR as i32,
+            params.filter.gl_mag_filter() as i32,
         );
         gl.tex_parameter_i32(
             glow::TEXTURE_2D,
             glow::TEXTURE_WRAP_S,
-            glow::REPEAT as i32,
+            params.wrap.gl_wrap() as i32,
         );
         gl.tex_parameter_i32(
             glow::TEXTURE_2D,
             glow::TEXTURE_WRAP_T,
-            glow::REPEAT as i32,
+            params.wrap.gl_wrap() as i32,
         );
-        
         gl.bind_texture(glow::TEXTURE_2D, None);
-        
-        Ok(TextureHandle {
-            path: path.to_path_buf(),
-            texture_id: Some(texture),
-            width,
-            height,
-        })
     }
 }
 
@@ -106,213 +151,293 @@ pub fn delete_texture(gl: &glow::Context, texture: glow::Texture) {
     }
 }
 
+// ==========================================
+// EXPORT
+// ==========================================
+
+/// Image export that replays feedback frames over several UI frames
+pub struct FeedbackReplay {
+    width: u32,
+    height: u32,
+    fps: u32,
+    start: FrameInputs,
+    targets: PassTargets,
+    first_frame: u32,
+    next_frame: u32,
+    last_frame: u32,
+}
+
 impl ShaderApp {
-    pub fn export_image(&self) {
+    pub fn export_image(&mut self) {
         let width = self.export_resolution[0];
         let height = self.export_resolution[1];
         
         log::info!("Exporting image at {}x{}", width, height);
+        if let Err(e) = self.check_tiled_export(width, height) {
+            log::error!("{}", e);
+            return;
+        }
         
-        let pixels = if self.post_process_enabled && self.post_process_renderer.is_some() {
-            // Two-pass export
-            self.render_two_pass_to_buffer(self.time, width, height)
-        } else {
-            // Single-pass export
-            self.render_frame_to_buffer(self.time, width, height)
-        };
+        let inputs = self.export_inputs(egui::Vec2::new(width as f32, height as f32));
+        let mut targets = self.export_targets(width, height);
+        if self.uses_feedback() {
+            // Feedback depends on every earlier frame, replay them at the video frame rate
+            let fps = self.video_fps;
+            let last_frame = (self.time * fps as f32).round() as u32;
+            let first_frame = last_frame.saturating_sub(MAX_FEEDBACK_REPLAY_FRAMES - 1);
+            if first_frame > 0 {
+                log::warn!(
+                    "Replaying only the last {} of {} frames for feedback passes, feedback starts from black at frame {}",
+                    MAX_FEEDBACK_REPLAY_FRAMES, last_frame + 1, first_frame,
+                );
+            } else {
+                log::info!("Replaying {} frames for feedback passes", last_frame + 1);
+            }
+            *self.export_progress.lock() = Some(ExportProgress {
+                current_frame: 0,
+                total_frames: last_frame - first_frame + 1,
+                status: "Replaying feedback...".to_string(),
+            });
+            self.image_replay = Some(FeedbackReplay {
+                width,
+                height,
+                fps,
+                start: inputs,
+                targets,
+                first_frame,
+                next_frame: first_frame,
+                last_frame,
+            });
+            return;
+        }
         
+        // The export date is the date at time zero
+        let inputs = FrameInputs { date: advance_date(inputs.date, inputs.time), ..inputs };
+        let pixels = self.render_export_frame(&inputs, width, height, &mut targets);
+        targets.destroy(&self.gl);
         if let Some(pixels) = pixels {
-            std::thread::spawn(move || {
-                if let Some(path) = rfd::FileDialog::new()
-                    .add_filter("PNG Image", &["png"])
-                    .set_file_name("shader_export.png")
-                    .save_file()
-                {
-                    match image::save_buffer(
-                        &path,
-                        &pixels,
-                        width,
-                        height,
-                        image::ColorType::Rgba8,
-                    ) {
-                        Ok(_) => log::info!("Image exported successfully to {:?}", path),
-                        Err(e) => log::error!("Failed to save image: {}", e),
-                    }
-                }
-            });
+            save_image(pixels, width, height);
         }
     }
 
-    pub fn render_frame_to_buffer(&self, time: f32, width: u32, height: u32) -> Option<Vec<u8>> {
-        unsafe {
-            use glow::HasContext as _;
-            let gl = &*self.gl;
-            
-            let fbo = gl.create_framebuffer().ok()?;
-            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
-            
-            let texture = gl.create_texture().ok()?;
-            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
-            gl.tex_image_2d(
-                glow::TEXTURE_2D, 0, glow::RGBA as i32,
-                width as i32, height as i32, 0,
-                glow::RGBA, glow::UNSIGNED_BYTE, None,
-            );
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
-            
-            gl.framebuffer_texture_2d(
-                glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0,
-                glow::TEXTURE_2D, Some(texture), 0,
-            );
-            
-            if gl.check_framebuffer_status(glow::FRAMEBUFFER) != glow::FRAMEBUFFER_COMPLETE {
-                log::error!("Framebuffer is not complete");
-                gl.delete_texture(texture);
-                gl.delete_framebuffer(fbo);
-                return None;
+    /// Render the next batch of feedback frames of a running image export, saving it after the last
+    pub fn poll_image_export(&mut self) {
+        let Some(mut replay) = self.image_replay.take() else {
+            return;
+        };
+        
+        let started = Instant::now();
+        let mut pixels = None;
+        while replay.next_frame <= replay.last_frame && started.elapsed() < REPLAY_BUDGET {
+            let inputs = export_frame_inputs(&replay.start, replay.next_frame, replay.fps);
+            pixels = self.render_export_frame(&inputs, replay.width, replay.height, &mut replay.targets);
+            if pixels.is_none() {
+                log::error!("Failed to render frame {}", replay.next_frame);
+                replay.targets.destroy(&self.gl);
+                *self.export_progress.lock() = None;
+                return;
             }
-            
-            gl.viewport(0, 0, width as i32, height as i32);
-            gl.clear_color(0.0, 0.0, 0.0, 1.0);
-            gl.clear(glow::COLOR_BUFFER_BIT);
-            
-            let size = egui::Vec2::new(width as f32, height as f32);
-            self.shader_renderer.lock().paint(gl, time, size, &self.uniforms);
-            
-            let mut pixels = vec![0u8; (width * height * 4) as usize];
-            gl.read_pixels(
-                0, 0, width as i32, height as i32,
-                glow::RGBA, glow::UNSIGNED_BYTE,
-                glow::PixelPackData::Slice(&mut pixels),
-            );
-            
-            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-            gl.delete_texture(texture);
-            gl.delete_framebuffer(fbo);
-            
-            // Flip vertically
-            let mut flipped = vec![0u8; pixels.len()];
-            for y in 0..height {
-                let src_row = &pixels[(y * width * 4) as usize..((y + 1) * width * 4) as usize];
-                let dst_y = height - 1 - y;
-                let dst_row = &mut flipped[(dst_y * width * 4) as usize..((dst_y + 1) * width * 4) as usize];
-                dst_row.copy_from_slice(src_row);
+            replay.next_frame += 1;
+        }
+        
+        let total_frames = replay.last_frame - replay.first_frame + 1;
+        let done = replay.next_frame - replay.first_frame;
+        if done < total_frames {
+            *self.export_progress.lock() = Some(ExportProgress {
+                current_frame: done,
+                total_frames,
+                status: format!("Replaying feedback frame {}/{}", done, total_frames),
+            });
+            self.image_replay = Some(replay);
+            return;
+        }
+        
+        replay.targets.destroy(&self.gl);
+        *self.export_progress.lock() = None;
+        if let Some(pixels) = pixels {
+            save_image(pixels, replay.width, replay.height);
+        }
+    }
+
+    pub fn cancel_image_export(&mut self) {
+        if let Some(mut replay) = self.image_replay.take() {
+            replay.targets.destroy(&self.gl);
+            *self.export_progress.lock() = None;
+            log::info!("Image export cancelled");
+        }
+    }
+
+    /// Frame inputs for an export, with `u_date` at the export date instead of the wall clock
+    pub(super) fn export_inputs(&self, resolution: egui::Vec2) -> FrameInputs {
+        FrameInputs {
+            date: self.export_date,
+            ..self.frame_inputs(resolution)
+        }
+    }
+
+    /// Fresh pass targets at export size (times the supersampling factor), so feedback starts from black
+    fn export_targets(&self, width: u32, height: u32) -> PassTargets {
+        let factor = self.export_supersampling;
+        let mut targets = PassTargets::default();
+        if self.export_needs_tiles(width, height) {
+            // Tiled exports create targets per tile
+            return targets;
+        }
+        targets.ensure(&self.gl, &self.target_specs(), width * factor, height * factor);
+        targets
+    }
+
+    /// Any pass reads its previous frame
+    fn uses_feedback(&self) -> bool {
+        self.target_specs().iter().any(|spec| spec.feedback)
+    }
+
+    /// The supersampled export has a side above `EXPORT_TILE_SIZE`. Frames with passes that can't
+    /// be tiled still render in one framebuffer while they fit the GPU limit.
+    pub fn export_needs_tiles(&self, width: u32, height: u32) -> bool {
+        let side = width.max(height) * self.export_supersampling;
+        side > self.max_texture_size || (side > EXPORT_TILE_SIZE && self.supports_tiles())
+    }
+
+    /// Tiles only stitch seamlessly when no pass samples another pass's texture
+    fn supports_tiles(&self) -> bool {
+        self.target_specs().iter().all(|spec| spec.name == OUTPUT_TARGET_NAME)
+    }
+
+    fn check_tiled_export(&self, width: u32, height: u32) -> Result<(), String> {
+        if self.export_needs_tiles(width, height) && !self.supports_tiles() {
+            return Err(format!(
+                "A {}x{} export exceeds the GPU limit of {}px and is rendered in tiles, \
+                 which only supports the main shader: switch off buffers, feedback and post-processing",
+                width * self.export_supersampling, height * self.export_supersampling, self.max_texture_size,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Advance the feedback targets and render one export frame
+    fn render_export_frame(&self, inputs: &FrameInputs, width: u32, height: u32, targets: &mut PassTargets) -> Option<Vec<u8>> {
+        if self.export_needs_tiles(width, height) {
+            return self.render_tiled_frame(inputs, width, height);
+        }
+        targets.swap();
+        self.render_frame_to_buffer(inputs, width, height, targets)
+    }
+
+    /// Render the frame tile by tile at one shared virtual resolution and stitch the rows, top row first.
+    /// Supersampled tiles render a margin that the downsampling filter reads and that is cropped
+    /// afterwards, so tile edges filter the same neighbours as the rest of the image.
+    fn render_tiled_frame(&self, inputs: &FrameInputs, width: u32, height: u32) -> Option<Vec<u8>> {
+        let factor = self.export_supersampling;
+        let apron = if factor > 1 { self.export_filter.radius() } else { 0 };
+        let tile_size = (EXPORT_TILE_SIZE.min(self.max_texture_size) / factor).saturating_sub(2 * apron).max(1);
+        log::info!(
+            "Rendering {}x{} in {} tiles of up to {}px",
+            width, height, width.div_ceil(tile_size) * height.div_ceil(tile_size), tile_size,
+        );
+        
+        let row_bytes = width as usize * 4;
+        let mut image = vec![0u8; row_bytes * height as usize];
+        for tile_y in (0..height).step_by(tile_size as usize) {
+            for tile_x in (0..width).step_by(tile_size as usize) {
+                let tile_width = tile_size.min(width - tile_x);
+                let tile_height = tile_size.min(height - tile_y);
+                // The image edges clamp like an untiled export, so margins stop there
+                let left = apron.min(tile_x);
+                let bottom = apron.min(tile_y);
+                let right = apron.min(width - tile_x - tile_width);
+                let top_margin = apron.min(height - tile_y - tile_height);
+                let (render_x, render_y) = (tile_x - left, tile_y - bottom);
+                let render_width = left + tile_width + right;
+                let render_height = bottom + tile_height + top_margin;
+                let tile_inputs = FrameInputs {
+                    tile: [render_x as f32, render_y as f32, render_width as f32, render_height as f32],
+                    ..*inputs
+                };
+                
+                let mut targets = self.export_targets(render_width, render_height);
+                let pixels = self.render_frame_to_buffer(&tile_inputs, render_width, render_height, &targets);
+                targets.destroy(&self.gl);
+                let pixels = pixels?;
+                
+                // Tile rows come top first, while tile_y counts from the bottom of the image
+                let top = (height - tile_y - tile_height) as usize;
+                let render_row_bytes = render_width as usize * 4;
+                let tile_row_bytes = tile_width as usize * 4;
+                let rows = pixels.chunks_exact(render_row_bytes)
+                    .skip(top_margin as usize)
+                    .take(tile_height as usize);
+                for (row, render_row) in rows.enumerate() {
+                    let tile_row = &render_row[left as usize * 4..][..tile_row_bytes];
+                    let start = (top + row) * row_bytes + tile_x as usize * 4;
+                    image[start..start + tile_row_bytes].copy_from_slice(tile_row);
+                }
             }
-            
-            Some(flipped)
         }
+        Some(image)
     }
 
-    // NEW: Two-pass rendering for export
-    pub fn render_two_pass_to_buffer(&self, time: f32, width: u32, height: u32) -> Option<Vec<u8>> {
-        use glow::HasContext as _;
+    /// Render one frame (or tile) at `width`x`height`, supersampled when enabled, and read it back top row first
+    pub fn render_frame_to_buffer(&self, inputs: &FrameInputs, width: u32, height: u32, targets: &PassTargets) -> Option<Vec<u8>> {
+        let factor = self.export_supersampling;
+        let (render_width, render_height) = (width * factor, height * factor);
+        let inputs = FrameInputs {
+            resolution: inputs.resolution * factor as f32,
+            mouse: inputs.mouse.scaled(egui::Vec2::splat(factor as f32)),
+            tile: inputs.tile.map(|value| value * factor as f32),
+            ..*inputs
+        };
+        let plan = self.frame_plan(targets);
         
-        unsafe {
-            let gl = &*self.gl;
-            
-            // === PASS 1: Render main shader to intermediate texture ===
-            let fbo1 = gl.create_framebuffer().ok()?;
-            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo1));
-            
-            let tex1 = gl.create_texture().ok()?;
-            gl.bind_texture(glow::TEXTURE_2D, Some(tex1));
-            gl.tex_image_2d(
-                glow::TEXTURE_2D, 0, glow::RGBA as i32,
-                width as i32, height as i32, 0,
-                glow::RGBA, glow::UNSIGNED_BYTE, None,
-            );
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
-            
-            gl.framebuffer_texture_2d(
-                glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0,
-                glow::TEXTURE_2D, Some(tex1), 0,
-            );
-            
-            if gl.check_framebuffer_status(glow::FRAMEBUFFER) != glow::FRAMEBUFFER_COMPLETE {
-                log::error!("Pass 1 framebuffer incomplete");
-                gl.delete_texture(tex1);
-                gl.delete_framebuffer(fbo1);
+        // Clamped and quantised only here, whatever the passes render in
+        let output = match RenderTarget::new(&self.gl, width, height, TargetFormat::Rgba8) {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("Failed to create export framebuffer: {}", e);
                 return None;
             }
-            
-            gl.viewport(0, 0, width as i32, height as i32);
-            gl.clear_color(0.0, 0.0, 0.0, 1.0);
-            gl.clear(glow::COLOR_BUFFER_BIT);
-            
-            let size = egui::Vec2::new(width as f32, height as f32);
-            self.shader_renderer.lock().paint(gl, time, size, &self.uniforms);
-            
-            // === PASS 2: Render post-process to final texture ===
-            let fbo2 = gl.create_framebuffer().ok()?;
-            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo2));
-            
-            let tex2 = gl.create_texture().ok()?;
-            gl.bind_texture(glow::TEXTURE_2D, Some(tex2));
-            gl.tex_image_2d(
-                glow::TEXTURE_2D, 0, glow::RGBA as i32,
-                width as i32, height as i32, 0,
-                glow::RGBA, glow::UNSIGNED_BYTE, None,
-            );
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
-            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
-            
-            gl.framebuffer_texture_2d(
-                glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0,
-                glow::TEXTURE_2D, Some(tex2), 0,
-            );
-            
-            if gl.check_framebuffer_status(glow::FRAMEBUFFER) != glow::FRAMEBUFFER_COMPLETE {
-                log::error!("Pass 2 framebuffer incomplete");
-                gl.delete_texture(tex1);
-                gl.delete_framebuffer(fbo1);
-                gl.delete_texture(tex2);
-                gl.delete_framebuffer(fbo2);
-                return None;
+        };
+        // Supersampled frames keep full precision until they are filtered down
+        let supersampled = if factor > 1 {
+            match RenderTarget::new(&self.gl, render_width, render_height, TargetFormat::Rgba16F) {
+                Ok(supersampled) => Some(supersampled),
+                Err(e) => {
+                    log::error!("Failed to create {}x supersampling framebuffer: {}", factor, e);
+                    output.destroy(&self.gl);
+                    return None;
+                }
             }
+        } else {
+            None
+        };
+        let frame_target = supersampled.as_ref().unwrap_or(&output);
+        let viewport = [0, 0, render_width as i32, render_height as i32];
+        
+        unsafe {
+            use glow::HasContext as _;
+            let gl = &*self.gl;
             
-            gl.viewport(0, 0, width as i32, height as i32);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(frame_target.fbo));
+            gl.viewport(0, 0, render_width as i32, render_height as i32);
             gl.clear_color(0.0, 0.0, 0.0, 1.0);
             gl.clear(glow::COLOR_BUFFER_BIT);
+            plan.render(gl, &inputs, Some(frame_target.fbo), viewport);
             
-            // Create post-process uniforms with main pass texture
-            let mut post_uniforms = self.post_process_uniforms.clone();
-            post_uniforms.insert(
-                "u_mainPass".to_string(),
-                super::data::UniformInfo {
-                    uniform_type: super::data::UniformType::Sampler2D,
-                    value: super::data::UniformValue::Sampler2D(Some(
-                        super::data::TextureHandle {
-                            path: std::path::PathBuf::from("[export_pass1]"),
-                            texture_id: Some(tex1),
-                            width,
-                            height,
-                        }
-                    )),
-                },
-            );
-            
-            if let Some(post_renderer) = &self.post_process_renderer {
-                post_renderer.lock().paint(gl, time, size, &post_uniforms);
+            if let Some(supersampled) = &supersampled {
+                render_downsample(gl, &self.downsample_renderer.lock(), supersampled, factor, self.export_filter, &output);
+                supersampled.destroy(gl);
             }
             
-            // Read pixels from final framebuffer
             let mut pixels = vec![0u8; (width * height * 4) as usize];
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(output.fbo));
             gl.read_pixels(
                 0, 0, width as i32, height as i32,
                 glow::RGBA, glow::UNSIGNED_BYTE,
                 glow::PixelPackData::Slice(&mut pixels),
             );
             
-            // Cleanup
             gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-            gl.delete_texture(tex1);
-            gl.delete_framebuffer(fbo1);
-            gl.delete_texture(tex2);
-            gl.delete_framebuffer(fbo2);
+            output.destroy(gl);
             
             // Flip vertically
             let mut flipped = vec![0u8; pixels.len()];
@@ -335,6 +460,10 @@ impl ShaderApp {
         
         log::info!("Starting video export: {}x{} @ {}fps, {} frames", 
                    width, height, fps, total_frames);
+        if let Err(e) = self.check_tiled_export(width, height) {
+            log::error!("{}", e);
+            return;
+        }
         
         let output_path = match rfd::FileDialog::new()
             .add_filter("MP4 Video", &["mp4"])
@@ -396,11 +525,11 @@ impl ShaderApp {
             status: "Rendering and encoding...".to_string(),
         });
         
-        // Determine which rendering method to use
-        let use_post_process = self.post_process_enabled && self.post_process_renderer.is_some();
-        
+        let size = egui::Vec2::new(width as f32, height as f32);
+        let start_inputs = self.export_inputs(size);
+        let mut targets = self.export_targets(width, height);
         for frame in 0..total_frames {
-            let time = frame as f32 / fps as f32;
+            let inputs = export_frame_inputs(&start_inputs, frame, fps);
             
             if frame % 10 == 0 {
                 *self.export_progress.lock() = Some(ExportProgress {
@@ -410,25 +539,23 @@ impl ShaderApp {
                 });
             }
             
-            // Render with or without post-processing
-            let pixels = if use_post_process {
-                self.render_two_pass_to_buffer(time, width, height)
-            } else {
-                self.render_frame_to_buffer(time, width, height)
-            };
+            let pixels = self.render_export_frame(&inputs, width, height, &mut targets);
             
             if let Some(pixels) = pixels {
                 if let Err(e) = stdin.write_all(&pixels) {
                     log::error!("Failed to write frame {}: {}", frame, e);
                     *self.export_progress.lock() = None;
+                    targets.destroy(&self.gl);
                     return;
                 }
             } else {
                 log::error!("Failed to render frame {}", frame);
                 *self.export_progress.lock() = None;
+                targets.destroy(&self.gl);
                 return;
             }
         }
+        targets.destroy(&self.gl);
         
         drop(stdin);
         
@@ -467,6 +594,42 @@ impl ShaderApp {
     }
 }
 
+/// Inputs for an exported frame: time, frame index and date advance at the export rate from
+/// `start`, not the wall clock
+pub(super) fn export_frame_inputs(start: &FrameInputs, frame: u32, fps: u32) -> FrameInputs {
+    let time = frame as f32 / fps as f32;
+    FrameInputs {
+        time,
+        time_delta: 1.0 / fps as f32,
+        frame,
+        frame_rate: fps as f32,
+        date: advance_date(start.date, time),
+        ..*start
+    }
+}
+
+/// Ask for a path on a background thread and save the exported pixels as PNG
+fn save_image(pixels: Vec<u8>, width: u32, height: u32) {
+    std::thread::spawn(move || {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG Image", &["png"])
+            .set_file_name("shader_export.png")
+            .save_file()
+        {
+            match image::save_buffer(
+                &path,
+                &pixels,
+                width,
+                height,
+                image::ColorType::Rgba8,
+            ) {
+                Ok(_) => log::info!("Image exported successfully to {:?}", path),
+                Err(e) => log::error!("Failed to save image: {}", e),
+            }
+        }
+    });
+}
+
 /// Helper for flipping raw pixel data (used by export functions)
 fn flip_image_vertically_raw(data: &[u8], width: u32, height: u32) -> Vec<u8> {
     let mut flipped = vec![0u8; data.len()];
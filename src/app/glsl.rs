@@ -0,0 +1,1003 @@
+use std::collections::HashMap;
+
+/// A single uniform declaration found in GLSL source
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniformDeclaration {
+    pub name: String,
+    pub type_name: String,
+    pub array_len: Option<usize>,
+    /// 1-based source line of the uniform's name
+    pub line: usize,
+    /// `@name(args)` annotations from comments on the declaration's lines
+    pub annotations: Vec<Annotation>,
+    /// Group from the closest `@group("Name")` marker at or above the declaration
+    pub group: Option<String>,
+    /// Declared by the Shadertoy wrapper, not the user's file
+    pub injected: bool,
+}
+
+/// A `@name(arg, ...)` annotation found in a comment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    pub args: Vec<AnnotationArg>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationArg {
+    Number(f64),
+    String(String),
+    Ident(String),
+}
+
+impl AnnotationArg {
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            AnnotationArg::Number(n) => Some(*n),
+            AnnotationArg::Ident(s) if s == "true" => Some(1.0),
+            AnnotationArg::Ident(s) if s == "false" => Some(0.0),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AnnotationArg::String(s) | AnnotationArg::Ident(s) => Some(s),
+            AnnotationArg::Number(_) => None,
+        }
+    }
+}
+
+impl Annotation {
+    /// All arguments that are numbers
+    pub fn numbers(&self) -> Vec<f64> {
+        self.args.iter().filter_map(AnnotationArg::as_number).collect()
+    }
+
+    pub fn first_str(&self) -> Option<&str> {
+        self.args.first().and_then(AnnotationArg::as_str)
+    }
+}
+
+struct Comment {
+    /// 1-based line the comment starts on
+    line: usize,
+    text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Number(String),
+    Punct(&'static str),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+}
+
+impl Token {
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.kind, TokenKind::Punct(q) if q == p)
+    }
+
+    fn ident(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::Ident(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+// Multi-character operators must come before their single-character prefixes
+const PUNCTUATION: &[&str] = &[
+    "<<=", ">>=", "&&", "||", "^^", "==", "!=", "<=", ">=", "<<", ">>", "++", "--",
+    "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=",
+    "(", ")", "[", "]", "{", "}", ";", ",", ".", "=", "+", "-", "*", "/", "%",
+    "<", ">", "!", "~", "&", "|", "^", "?", ":", "#",
+];
+
+const TYPE_QUALIFIERS: &[&str] = &[
+    "lowp", "mediump", "highp", "const", "invariant", "precise",
+    "flat", "smooth", "noperspective", "centroid", "sample",
+    "coherent", "volatile", "restrict", "readonly", "writeonly",
+];
+
+/// Parse all active uniform declarations, ignoring comments and disabled preprocessor blocks
+pub fn parse_uniform_declarations(source: &str) -> Vec<UniformDeclaration> {
+    // Declarations injected by the Shadertoy wrapper have no line in the user's file
+    if let Some(body) = source.strip_prefix(SHADERTOY_HEADER) {
+        let mut declarations = parse_declarations(SHADERTOY_HEADER);
+        for declaration in &mut declarations {
+            declaration.line = 0;
+            declaration.injected = true;
+        }
+        declarations.extend(parse_declarations(body));
+        return declarations;
+    }
+    parse_declarations(source)
+}
+
+fn parse_declarations(source: &str) -> Vec<UniformDeclaration> {
+    let (stripped, comments) = strip_comments(source);
+    let (tokens, lines) = preprocess(&stripped);
+    // Comments take the reported line number of their line; those in disabled blocks don't count
+    let comments: Vec<Comment> = comments.into_iter()
+        .filter_map(|comment| {
+            let &(line, active) = lines.get(comment.line.checked_sub(1)?)?;
+            active.then_some(Comment { line, text: comment.text })
+        })
+        .collect();
+    let mut parser = Parser { tokens: &tokens, pos: 0, comments: &comments };
+    parser.parse_translation_unit()
+}
+
+/// Wrap Shadertoy-style sources that define `mainImage` but no `main`
+pub fn wrap_shadertoy(source: &str) -> Option<String> {
+    let (stripped, _) = strip_comments(source);
+    let (tokens, _) = preprocess(&stripped);
+
+    let defines_function = |name: &str| {
+        let mut depth = 0usize;
+        tokens.windows(2).any(|pair| {
+            if pair[0].is_punct("{") {
+                depth += 1;
+            } else if pair[0].is_punct("}") {
+                depth = depth.saturating_sub(1);
+            }
+            depth == 0 && pair[0].ident() == Some(name) && pair[1].is_punct("(")
+        })
+    };
+
+    if !defines_function("mainImage") || defines_function("main") {
+        return None;
+    }
+
+    // Drop any `#version` line; `#line` keeps compiler errors pointing at the original lines
+    let body: Vec<&str> = source
+        .lines()
+        .map(|line| if line.trim_start().starts_with("#version") { "" } else { line })
+        .collect();
+
+    Some(format!(
+        "{}\n#line 1\n{}\n{}",
+        SHADERTOY_HEADER,
+        body.join("\n"),
+        SHADERTOY_FOOTER,
+    ))
+}
+
+const SHADERTOY_HEADER: &str = "#version 330 core
+precision highp float;
+precision highp int;
+
+in vec2 v_uv;
+out vec4 shadertoy_fragColor;
+
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float iTimeDelta;
+uniform float iFrameRate;
+uniform int iFrame;
+uniform vec4 iMouse;
+uniform vec4 iDate;
+uniform vec3 iChannelResolution[4];
+uniform sampler2D iChannel0;
+uniform sampler2D iChannel1;
+uniform sampler2D iChannel2;
+uniform sampler2D iChannel3;";
+
+// Derive fragCoord from the UV so it stays relative to the render target, not the window
+const SHADERTOY_FOOTER: &str = "
+void main() {
+    vec4 color = vec4(0.0, 0.0, 0.0, 1.0);
+    mainImage(color, v_uv * iResolution.xy);
+    shadertoy_fragColor = color;
+}";
+
+// ==========================================
+// COMMENTS
+// ==========================================
+
+/// Replace comments with whitespace, keeping newlines so line numbers stay stable
+fn strip_comments(source: &str) -> (String, Vec<Comment>) {
+    let mut out = String::with_capacity(source.len());
+    let mut comments = Vec::new();
+    let mut line = 1;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '/' && chars.peek() == Some(&'/') {
+            chars.next();
+            let mut text = String::new();
+            while let Some(&next) = chars.peek() {
+                if next == '\n' {
+                    break;
+                }
+                text.push(next);
+                chars.next();
+            }
+            comments.push(Comment { line, text });
+            out.push(' ');
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            let start_line = line;
+            let mut text = String::new();
+            let mut prev = '\0';
+            for next in chars.by_ref() {
+                if next == '\n' {
+                    out.push('\n');
+                    line += 1;
+                }
+                if prev == '*' && next == '/' {
+                    text.pop();
+                    break;
+                }
+                text.push(next);
+                prev = next;
+            }
+            comments.push(Comment { line: start_line, text });
+            out.push(' ');
+        } else {
+            if c == '\n' {
+                line += 1;
+            }
+            out.push(c);
+        }
+    }
+
+    (out, comments)
+}
+
+/// Parse every `@name` or `@name(args)` annotation in a comment
+pub fn parse_annotations(text: &str) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '@' {
+            continue;
+        }
+
+        let mut name = String::new();
+        while let Some(&next) = chars.peek() {
+            if !next.is_alphanumeric() && next != '_' {
+                break;
+            }
+            name.push(next);
+            chars.next();
+        }
+        if name.is_empty() {
+            continue;
+        }
+
+        let mut args = Vec::new();
+        if chars.peek() == Some(&'(') {
+            chars.next();
+            let mut current = String::new();
+            let mut quoted: Option<String> = None;
+
+            while let Some(next) = chars.next() {
+                match next {
+                    '"' => {
+                        let mut string = String::new();
+                        while let Some(ch) = chars.next() {
+                            match ch {
+                                '\\' => string.extend(chars.next()),
+                                '"' => break,
+                                _ => string.push(ch),
+                            }
+                        }
+                        quoted = Some(string);
+                    }
+                    ',' | ')' => {
+                        if let Some(string) = quoted.take() {
+                            args.push(AnnotationArg::String(string));
+                        } else {
+                            let token = current.trim();
+                            if let Ok(number) = token.parse::<f64>() {
+                                args.push(AnnotationArg::Number(number));
+                            } else if !token.is_empty() {
+                                args.push(AnnotationArg::Ident(token.to_string()));
+                            }
+                        }
+                        current.clear();
+                        if next == ')' {
+                            break;
+                        }
+                    }
+                    _ => current.push(next),
+                }
+            }
+        }
+
+        annotations.push(Annotation { name, args });
+    }
+
+    annotations
+}
+
+// ==========================================
+// TOKENIZER
+// ==========================================
+
+fn tokenize_line(text: &str, line: usize, tokens: &mut Vec<Token>) {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Ident(text[start..i].to_string()), line });
+        } else if c.is_ascii_digit() || (c == '.' && bytes.get(i + 1).is_some_and(|b| b.is_ascii_digit())) {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                // Exponent signs belong to the number literal
+                if matches!(bytes[i], b'e' | b'E') && matches!(bytes.get(i + 1), Some(b'+' | b'-'))
+                    && !text[start..i].starts_with("0x")
+                {
+                    i += 1;
+                }
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Number(text[start..i].to_string()), line });
+        } else if let Some(p) = PUNCTUATION.iter().find(|p| text[i..].starts_with(**p)) {
+            tokens.push(Token { kind: TokenKind::Punct(p), line });
+            i += p.len();
+        } else {
+            // Unknown character (e.g. non-ASCII); skip it
+            i += text[i..].chars().next().map_or(1, |ch| ch.len_utf8());
+        }
+    }
+}
+
+// ==========================================
+// PREPROCESSOR
+// ==========================================
+
+struct Conditional {
+    /// Whether the enclosing block is active
+    parent_active: bool,
+    /// Whether any branch of this conditional has been taken
+    taken: bool,
+    /// Whether the current branch is active
+    active: bool,
+}
+
+/// Evaluate preprocessor directives and tokenize the remaining active lines.
+/// Also returns the reported line number of every source line and whether it is active.
+fn preprocess(source: &str) -> (Vec<Token>, Vec<(usize, bool)>) {
+    let mut tokens = Vec::new();
+    let mut line_info = Vec::new();
+    let mut defines: HashMap<String, Vec<Token>> = HashMap::new();
+    let mut stack: Vec<Conditional> = Vec::new();
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut index = 0;
+    // Adjusted by `#line` so reported lines match the original file
+    let mut line_offset: isize = 0;
+
+    while index < lines.len() {
+        let line_no = (index as isize + 1 + line_offset).max(0) as usize;
+
+        let first = index;
+        // Join backslash continuations into one logical line
+        let mut logical = lines[index].to_string();
+        while logical.ends_with('\\') && index + 1 < lines.len() {
+            logical.pop();
+            index += 1;
+            logical.push_str(lines[index]);
+        }
+        index += 1;
+
+        let active = stack.last().is_none_or(|c| c.active);
+        line_info.extend((first..index).map(|_| (line_no, active)));
+        let trimmed = logical.trim_start();
+
+        if let Some(directive) = trimmed.strip_prefix('#') {
+            let directive = directive.trim_start();
+            let keyword_end = directive
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(directive.len());
+            let (keyword, rest) = directive.split_at(keyword_end);
+            let rest = rest.trim();
+
+            match keyword {
+                "if" | "ifdef" | "ifndef" => {
+                    let condition = active && match keyword {
+                        "ifdef" => defines.contains_key(first_word(rest)),
+                        "ifndef" => !defines.contains_key(first_word(rest)),
+                        _ => eval_condition(rest, line_no, &defines),
+                    };
+                    stack.push(Conditional { parent_active: active, taken: condition, active: condition });
+                }
+                "elif" => {
+                    if let Some(cond) = stack.last_mut() {
+                        let condition = cond.parent_active && !cond.taken
+                            && eval_condition(rest, line_no, &defines);
+                        cond.active = condition;
+                        cond.taken |= condition;
+                    }
+                }
+                "else" => {
+                    if let Some(cond) = stack.last_mut() {
+                        cond.active = cond.parent_active && !cond.taken;
+                        cond.taken = true;
+                    }
+                }
+                "endif" => {
+                    stack.pop();
+                }
+                "define" if active => {
+                    let name_end = rest
+                        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                        .unwrap_or(rest.len());
+                    let (name, body) = rest.split_at(name_end);
+                    // Function-like macros are recorded for `defined` but never expanded
+                    let body = if body.starts_with('(') { "" } else { body };
+                    let mut body_tokens = Vec::new();
+                    tokenize_line(body, line_no, &mut body_tokens);
+                    defines.insert(name.to_string(), body_tokens);
+                }
+                "undef" if active => {
+                    defines.remove(first_word(rest));
+                }
+                "line" if active => {
+                    // The line after `#line N` is line N
+                    if let Ok(next_line) = first_word(rest).parse::<isize>() {
+                        line_offset = next_line - (index as isize + 1);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        if active {
+            let mut line_tokens = Vec::new();
+            tokenize_line(&logical, line_no, &mut line_tokens);
+            expand_macros(line_tokens, &defines, &mut tokens, 0);
+        }
+    }
+
+    (tokens, line_info)
+}
+
+fn first_word(text: &str) -> &str {
+    text.split_whitespace().next().unwrap_or("")
+}
+
+/// Substitute object-like macros, keeping the line of the original token
+fn expand_macros(input: Vec<Token>, defines: &HashMap<String, Vec<Token>>, out: &mut Vec<Token>, depth: usize) {
+    for token in input {
+        match token.ident().and_then(|name| defines.get(name)) {
+            Some(body) if depth < 16 => {
+                let body = body.iter().map(|t| Token { kind: t.kind.clone(), line: token.line }).collect();
+                expand_macros(body, defines, out, depth + 1);
+            }
+            _ => out.push(token),
+        }
+    }
+}
+
+fn eval_condition(expression: &str, line: usize, defines: &HashMap<String, Vec<Token>>) -> bool {
+    let mut raw = Vec::new();
+    tokenize_line(expression, line, &mut raw);
+
+    // Resolve `defined X` / `defined(X)` before macro expansion
+    let mut resolved = Vec::new();
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i].ident() == Some("defined") {
+            let parenthesized = raw.get(i + 1).is_some_and(|t| t.is_punct("("));
+            let name_index = if parenthesized { i + 2 } else { i + 1 };
+            let is_defined = raw.get(name_index)
+                .and_then(|t| t.ident())
+                .is_some_and(|name| defines.contains_key(name));
+            resolved.push(Token {
+                kind: TokenKind::Number(if is_defined { "1" } else { "0" }.to_string()),
+                line,
+            });
+            i = name_index + if parenthesized { 2 } else { 1 };
+        } else {
+            resolved.push(raw[i].clone());
+            i += 1;
+        }
+    }
+
+    let mut expanded = Vec::new();
+    expand_macros(resolved, defines, &mut expanded, 0);
+
+    let mut evaluator = ConstEvaluator { tokens: &expanded, pos: 0 };
+    evaluator.eval().unwrap_or(0) != 0
+}
+
+// ==========================================
+// CONSTANT EXPRESSIONS
+// ==========================================
+
+/// Integer expression evaluator used for `#if` conditions and array sizes
+struct ConstEvaluator<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl ConstEvaluator<'_> {
+    fn eval(&mut self) -> Option<i64> {
+        let value = self.binary(0)?;
+        (self.pos == self.tokens.len()).then_some(value)
+    }
+
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos)?.kind {
+            TokenKind::Punct(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Option<i64> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.peek_punct() {
+            let precedence = match op {
+                "||" => 1,
+                "&&" => 2,
+                "|" => 3,
+                "^" => 4,
+                "&" => 5,
+                "==" | "!=" => 6,
+                "<" | ">" | "<=" | ">=" => 7,
+                "<<" | ">>" => 8,
+                "+" | "-" => 9,
+                "*" | "/" | "%" => 10,
+                _ => break,
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(precedence + 1)?;
+            lhs = match op {
+                "||" => ((lhs != 0) || (rhs != 0)) as i64,
+                "&&" => ((lhs != 0) && (rhs != 0)) as i64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => (lhs == rhs) as i64,
+                "!=" => (lhs != rhs) as i64,
+                "<" => (lhs < rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                ">=" => (lhs >= rhs) as i64,
+                "<<" => lhs.checked_shl(rhs as u32)?,
+                ">>" => lhs.checked_shr(rhs as u32)?,
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                "/" => lhs.checked_div(rhs)?,
+                "%" => lhs.checked_rem(rhs)?,
+                _ => unreachable!(),
+            };
+        }
+
+        Some(lhs)
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+
+        match &token.kind {
+            TokenKind::Punct("!") => Some((self.unary()? == 0) as i64),
+            TokenKind::Punct("-") => Some(self.unary()?.wrapping_neg()),
+            TokenKind::Punct("+") => self.unary(),
+            TokenKind::Punct("~") => Some(!self.unary()?),
+            TokenKind::Punct("(") => {
+                let value = self.binary(0)?;
+                if self.peek_punct() != Some(")") {
+                    return None;
+                }
+                self.pos += 1;
+                Some(value)
+            }
+            TokenKind::Number(text) => parse_int_literal(text),
+            // Undefined identifiers evaluate to 0, as in C
+            TokenKind::Ident(_) => Some(0),
+            TokenKind::Punct(_) => None,
+        }
+    }
+}
+
+fn parse_int_literal(text: &str) -> Option<i64> {
+    let text = text.trim_end_matches(['u', 'U']);
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if text.len() > 1 && text.starts_with('0') {
+        i64::from_str_radix(&text[1..], 8).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+// ==========================================
+// DECLARATIONS
+// ==========================================
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    comments: &'a [Comment],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_translation_unit(&mut self) -> Vec<UniformDeclaration> {
+        let mut declarations = Vec::new();
+        let mut depth = 0usize;
+
+        while let Some(token) = self.peek() {
+            if token.is_punct("{") {
+                depth += 1;
+            } else if token.is_punct("}") {
+                depth = depth.saturating_sub(1);
+            } else if depth == 0 && token.ident() == Some("uniform") {
+                self.pos += 1;
+                self.parse_uniform_statement(&mut declarations);
+                continue;
+            }
+            self.pos += 1;
+        }
+
+        declarations
+    }
+
+    /// Parse the rest of a statement after the `uniform` keyword
+    fn parse_uniform_statement(&mut self, declarations: &mut Vec<UniformDeclaration>) {
+        let first = declarations.len();
+        let start_line = self.peek().map_or(0, |t| t.line);
+        self.parse_uniform_declarators(declarations);
+
+        // Annotations in comments on any line of the statement apply to every declarator
+        let end_line = self.tokens.get(self.pos.saturating_sub(1)).map_or(start_line, |t| t.line);
+        let annotations: Vec<Annotation> = self.comments.iter()
+            .filter(|c| (start_line..=end_line).contains(&c.line))
+            .flat_map(|c| parse_annotations(&c.text))
+            .collect();
+        let group = self.group_at(end_line);
+        for declaration in &mut declarations[first..] {
+            declaration.annotations = annotations.clone();
+            declaration.group = group.clone();
+        }
+    }
+
+    /// Most recent `@group` marker on or before `line`; `@group` without a name ends the group
+    fn group_at(&self, line: usize) -> Option<String> {
+        self.comments.iter()
+            .take_while(|c| c.line <= line)
+            .flat_map(|c| parse_annotations(&c.text))
+            .filter(|a| a.name == "group")
+            .last()
+            .and_then(|a| a.first_str().map(str::to_string))
+            .filter(|name| !name.is_empty())
+    }
+
+    fn parse_uniform_declarators(&mut self, declarations: &mut Vec<UniformDeclaration>) {
+        while self.peek().and_then(|t| t.ident()).is_some_and(|id| TYPE_QUALIFIERS.contains(&id)) {
+            self.pos += 1;
+        }
+
+        let Some(type_name) = self.peek().and_then(|t| t.ident()).map(str::to_string) else {
+            self.skip_statement();
+            return;
+        };
+        self.pos += 1;
+
+        // Interface blocks (`uniform Block { ... } instance;`) are not plain uniforms
+        if self.peek().is_some_and(|t| t.is_punct("{")) {
+            self.skip_statement();
+            return;
+        }
+
+        // Array size may be attached to the type (`uniform float[4] x;`).
+        // Unsized arrays and sizes that aren't constant can't be edited, so they are skipped.
+        let type_array_len = if self.peek().is_some_and(|t| t.is_punct("[")) {
+            let Some(len) = self.parse_array_len() else {
+                self.skip_statement();
+                return;
+            };
+            Some(len)
+        } else {
+            None
+        };
+
+        loop {
+            let Some(token) = self.peek() else { return };
+            let Some(name) = token.ident().map(str::to_string) else {
+                self.skip_statement();
+                return;
+            };
+            let line = token.line;
+            self.pos += 1;
+
+            let (array_len, sized) = if self.peek().is_some_and(|t| t.is_punct("[")) {
+                let len = self.parse_array_len();
+                (len, len.is_some())
+            } else {
+                (type_array_len, true)
+            };
+
+            // Skip initializers (`uniform float x = 1.0;`)
+            if self.peek().is_some_and(|t| t.is_punct("=")) {
+                self.skip_until_separator();
+            }
+
+            if sized {
+                declarations.push(UniformDeclaration {
+                    name,
+                    type_name: type_name.clone(),
+                    array_len,
+                    line,
+                    annotations: Vec::new(),
+                    group: None,
+                    injected: false,
+                });
+            }
+
+            match self.peek() {
+                Some(t) if t.is_punct(",") => self.pos += 1,
+                Some(t) if t.is_punct(";") => {
+                    self.pos += 1;
+                    return;
+                }
+                _ => {
+                    self.skip_statement();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Parse `[expr]` and evaluate the size, consuming the brackets
+    fn parse_array_len(&mut self) -> Option<usize> {
+        let start = self.pos + 1;
+        let mut depth = 0usize;
+
+        while let Some(token) = self.peek() {
+            if token.is_punct("[") {
+                depth += 1;
+            } else if token.is_punct("]") {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            } else if token.is_punct(";") {
+                return None;
+            }
+            self.pos += 1;
+        }
+
+        let end = self.pos.min(self.tokens.len());
+        self.pos += 1;
+
+        let mut evaluator = ConstEvaluator { tokens: &self.tokens[start..end], pos: 0 };
+        evaluator.eval().and_then(|len| usize::try_from(len).ok()).filter(|len| *len > 0)
+    }
+
+    /// Skip to the next `,` or `;` outside of any brackets
+    fn skip_until_separator(&mut self) {
+        let mut depth = 0usize;
+
+        while let Some(token) = self.peek() {
+            match token.kind {
+                TokenKind::Punct("(" | "[" | "{") => depth += 1,
+                TokenKind::Punct(")" | "]" | "}") => depth = depth.saturating_sub(1),
+                TokenKind::Punct("," | ";") if depth == 0 => return,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Skip past the next `;` outside of any brackets
+    fn skip_statement(&mut self) {
+        let mut depth = 0usize;
+
+        while let Some(token) = self.peek() {
+            let at_end = match token.kind {
+                TokenKind::Punct("(" | "[" | "{") => {
+                    depth += 1;
+                    false
+                }
+                TokenKind::Punct(")" | "]" | "}") => {
+                    depth = depth.saturating_sub(1);
+                    false
+                }
+                TokenKind::Punct(";") => depth == 0,
+                _ => false,
+            };
+            self.pos += 1;
+            if at_end {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::parse_uniforms;
+
+    fn names(source: &str) -> Vec<String> {
+        parse_uniform_declarations(source).into_iter().map(|d| d.name).collect()
+    }
+
+    fn declaration(source: &str, name: &str) -> UniformDeclaration {
+        parse_uniform_declarations(source).into_iter()
+            .find(|d| d.name == name)
+            .unwrap_or_else(|| panic!("{} not declared", name))
+    }
+
+    #[test]
+    fn comments_hide_declarations() {
+        let source = "\
+// uniform float line_comment;
+uniform float a; /* uniform float inline_block; */
+/*
+uniform float block;
+*/
+uniform float b;
+";
+        assert_eq!(names(source), ["a", "b"]);
+        assert_eq!(declaration(source, "b").line, 6);
+    }
+
+    #[test]
+    fn conditional_blocks() {
+        let source = "\
+#define A
+#if 0
+uniform float zero;
+#elif 1
+uniform float elif_taken;
+#elif 1
+uniform float elif_not_taken;
+#else
+uniform float else_branch;
+#endif
+#ifdef A
+    #ifdef B
+    uniform float b;
+    #else
+    uniform float not_b;
+    #endif
+    #ifndef B
+    uniform float ndef_b;
+    #endif
+#endif
+#if defined(A) && !defined(B)
+uniform float expression;
+#endif
+";
+        assert_eq!(names(source), ["elif_taken", "not_b", "ndef_b", "expression"]);
+    }
+
+    #[test]
+    fn several_declarators_in_one_statement() {
+        let source = "uniform float a, b = 2.0, c[3];";
+        assert_eq!(names(source), ["a", "b", "c"]);
+        assert_eq!(declaration(source, "c").array_len, Some(3));
+        assert!(parse_uniform_declarations(source).iter().all(|d| d.type_name == "float"));
+    }
+
+    #[test]
+    fn layout_and_precision_qualifiers() {
+        let source = "\
+precision highp float;
+layout(location = 3) uniform vec3 located;
+uniform highp int precise_int;
+uniform lowp sampler2D texture_input;
+";
+        let declarations = parse_uniform_declarations(source);
+        let types: Vec<(&str, &str)> = declarations.iter()
+            .map(|d| (d.name.as_str(), d.type_name.as_str()))
+            .collect();
+        assert_eq!(types, [("located", "vec3"), ("precise_int", "int"), ("texture_input", "sampler2D")]);
+    }
+
+    #[test]
+    fn array_sizes_from_macros() {
+        let source = "\
+#define COUNT 4
+#define TOTAL (COUNT * 2 + 1)
+uniform vec3 lights[COUNT];
+uniform float[TOTAL] weights;
+";
+        assert_eq!(declaration(source, "lights").array_len, Some(4));
+        assert_eq!(declaration(source, "weights").array_len, Some(9));
+    }
+
+    #[test]
+    fn unsized_arrays_and_unknown_types_are_skipped() {
+        let source = "\
+struct Light { vec3 color; };
+uniform float unsized[];
+uniform float unknown_size[UNDEFINED];
+uniform Light light;
+uniform Block { float member; } block;
+uniform float kept;
+";
+        assert_eq!(names(source), ["light", "kept"]);
+        // Declarations of types the editor doesn't know never become uniforms
+        let uniforms = parse_uniforms(source);
+        assert_eq!(uniforms.keys().collect::<Vec<_>>(), ["kept"]);
+    }
+
+    #[test]
+    fn line_directive_renumbers_lines() {
+        let source = "\
+uniform float before;
+#line 100
+uniform float after;
+
+uniform float later;
+";
+        assert_eq!(declaration(source, "before").line, 1);
+        assert_eq!(declaration(source, "after").line, 100);
+        assert_eq!(declaration(source, "later").line, 102);
+    }
+
+    #[test]
+    fn annotations_on_multi_line_declarations() {
+        let source = "\
+uniform float
+    speed // @range(0, 10)
+    ; // @step(0.5)
+uniform float other;
+";
+        let speed = declaration(source, "speed");
+        let annotations: Vec<&str> = speed.annotations.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(annotations, ["range", "step"]);
+        assert_eq!(speed.annotations[0].numbers(), [0.0, 10.0]);
+        assert!(declaration(source, "other").annotations.is_empty());
+    }
+
+    #[test]
+    fn groups_in_disabled_blocks_are_ignored() {
+        let source = "\
+// @group(\"Shown\")
+#if 0
+// @group(\"Hidden\")
+#endif
+uniform float a;
+";
+        assert_eq!(declaration(source, "a").group.as_deref(), Some("Shown"));
+    }
+
+    #[test]
+    fn shadertoy_wrapping_keeps_user_lines() {
+        let source = "\
+uniform float speed;
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    fragColor = vec4(speed);
+}
+";
+        let wrapped = wrap_shadertoy(source).expect("mainImage without main is wrapped");
+        assert_eq!(declaration(&wrapped, "speed").line, 1);
+        assert_eq!(declaration(&wrapped, "iChannel0").line, 0);
+        assert!(declaration(&wrapped, "iChannel0").injected);
+        assert!(wrap_shadertoy("void main() {}\nvoid mainImage(out vec4 c, in vec2 p) {}").is_none());
+    }
+}
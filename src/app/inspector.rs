@@ -0,0 +1,246 @@
+use crate::app::ShaderApp;
+use super::data::UniformType;
+use super::render_graph::MAIN_PASS_NAME;
+
+use std::sync::Arc;
+
+use egui::mutex::Mutex;
+use egui_glow::glow;
+use glow::HasContext;
+
+/// Pixels shown on each side of the inspected one in the loupe
+pub const LOUPE_RADIUS: u32 = 7;
+
+const LOUPE_SIZE: u32 = LOUPE_RADIUS * 2 + 1;
+
+/// On-screen size of one loupe pixel, in points
+const LOUPE_CELL: f32 = 8.0;
+
+// ==========================================
+// PIXEL READBACK
+// ==========================================
+
+/// Value of the inspected pixel in one stage of the frame
+#[derive(Debug, Clone)]
+pub struct PixelSample {
+    pub stage: String,
+    pub rgba: [f32; 4],
+}
+
+/// Pixel values read back from the last preview frame
+#[derive(Debug, Clone)]
+pub struct PixelReadback {
+    /// Render target pixel, origin at the bottom-left
+    pub pixel: [u32; 2],
+    /// Render target size
+    pub size: [u32; 2],
+    /// Main shader first, then every post-process stage and the output pass
+    pub samples: Vec<PixelSample>,
+    /// Stage the loupe shows
+    pub loupe_stage: String,
+    /// `LOUPE_SIZE`² pixels around `pixel`, bottom row first; outside the frame is transparent
+    pub loupe: Vec<[f32; 4]>,
+}
+
+impl PixelReadback {
+    /// Texture coordinate of the pixel centre, as `v_uv` sees it
+    pub fn uv(&self) -> [f32; 2] {
+        [
+            (self.pixel[0] as f32 + 0.5) / self.size[0] as f32,
+            (self.pixel[1] as f32 + 0.5) / self.size[1] as f32,
+        ]
+    }
+}
+
+/// Where one stage of the frame can be read once it is drawn
+pub struct ReadSource {
+    pub stage: String,
+    pub fbo: Option<glow::Framebuffer>,
+    /// Offset of the frame in the framebuffer, non-zero only for the screen
+    pub origin: [i32; 2],
+}
+
+/// Pixel to read back in the paint callback
+#[derive(Clone)]
+pub struct PixelRequest {
+    pub pixel: [u32; 2],
+    pub size: [u32; 2],
+    pub loupe_stage: Option<String>,
+}
+
+impl PixelRequest {
+    /// Read the pixel from every stage, and the loupe from the selected stage or the final image.
+    /// Leaves the default framebuffer bound.
+    pub fn read(&self, gl: &glow::Context, sources: &[ReadSource]) -> PixelReadback {
+        let [x, y] = self.pixel;
+        let samples = sources.iter()
+            .map(|source| PixelSample {
+                stage: source.stage.clone(),
+                rgba: read_region(gl, source, [x, y], [1, 1])[0],
+            })
+            .collect();
+
+        let loupe_source = self.loupe_stage.as_ref()
+            .and_then(|stage| sources.iter().find(|source| &source.stage == stage))
+            .or(sources.last());
+        let mut loupe = vec![[0.0; 4]; (LOUPE_SIZE * LOUPE_SIZE) as usize];
+        if let Some(source) = loupe_source {
+            // Only the part inside the frame is read
+            let [width, height] = self.size;
+            let (x0, y0) = (x.saturating_sub(LOUPE_RADIUS), y.saturating_sub(LOUPE_RADIUS));
+            let (x1, y1) = ((x + LOUPE_RADIUS + 1).min(width), (y + LOUPE_RADIUS + 1).min(height));
+            let region = read_region(gl, source, [x0, y0], [x1 - x0, y1 - y0]);
+            for (index, rgba) in region.into_iter().enumerate() {
+                let column = x0 + index as u32 % (x1 - x0) + LOUPE_RADIUS - x;
+                let row = y0 + index as u32 / (x1 - x0) + LOUPE_RADIUS - y;
+                loupe[(row * LOUPE_SIZE + column) as usize] = rgba;
+            }
+        }
+
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+        PixelReadback {
+            pixel: self.pixel,
+            size: self.size,
+            samples,
+            loupe_stage: loupe_source.map(|source| source.stage.clone()).unwrap_or_default(),
+            loupe,
+        }
+    }
+}
+
+/// RGBA floats of a block of pixels, bottom row first. Float targets keep values outside 0..1.
+fn read_region(gl: &glow::Context, source: &ReadSource, [x, y]: [u32; 2], [width, height]: [u32; 2]) -> Vec<[f32; 4]> {
+    let mut bytes = vec![0u8; (width * height * 16) as usize];
+    unsafe {
+        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, source.fbo);
+        gl.read_pixels(
+            source.origin[0] + x as i32, source.origin[1] + y as i32,
+            width as i32, height as i32,
+            glow::RGBA, glow::FLOAT,
+            glow::PixelPackData::Slice(&mut bytes),
+        );
+    }
+    bytes.chunks_exact(16)
+        .map(|pixel| {
+            let mut rgba = [0.0; 4];
+            for (component, value) in rgba.iter_mut().zip(pixel.chunks_exact(4)) {
+                *component = f32::from_ne_bytes([value[0], value[1], value[2], value[3]]);
+            }
+            rgba
+        })
+        .collect()
+}
+
+/// 0..255 value of a color component, as an 8-bit target stores it
+pub fn component_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// ==========================================
+// INSPECTOR
+// ==========================================
+
+/// Hover readout of the preview: pixel values per stage and a zoomed loupe
+#[derive(Default)]
+pub struct PixelInspector {
+    pub enabled: bool,
+    /// Pixel held by a right-click on the canvas, in render target pixels
+    pub pinned: Option<[u32; 2]>,
+    /// Stage shown in the loupe, the final image when unset
+    pub loupe_stage: Option<String>,
+    /// Written by the paint callback, shown on the next frame
+    pub readback: Arc<Mutex<Option<PixelReadback>>>,
+}
+
+impl PixelInspector {
+    /// Loupe grid around the inspected pixel, next to `anchor` and kept inside `clip`
+    pub fn paint_loupe(&self, painter: &egui::Painter, anchor: egui::Pos2, clip: egui::Rect) {
+        let Some(readback) = self.readback.lock().clone() else {
+            return;
+        };
+
+        let side = LOUPE_SIZE as f32 * LOUPE_CELL;
+        let label_height = 16.0;
+        let size = egui::vec2(side, side + label_height);
+        let mut min = anchor + egui::vec2(16.0, 16.0);
+        if min.x + size.x > clip.right() {
+            min.x = anchor.x - 16.0 - size.x;
+        }
+        if min.y + size.y > clip.bottom() {
+            min.y = anchor.y - 16.0 - size.y;
+        }
+        let frame = egui::Rect::from_min_size(min, size);
+        painter.rect_filled(frame.expand(2.0), 2.0, egui::Color32::from_black_alpha(200));
+
+        for (index, rgba) in readback.loupe.iter().enumerate() {
+            let column = index as u32 % LOUPE_SIZE;
+            // Rows come bottom first, the screen counts from the top
+            let row = LOUPE_SIZE - 1 - index as u32 / LOUPE_SIZE;
+            let cell = egui::Rect::from_min_size(
+                min + egui::vec2(column as f32, row as f32) * LOUPE_CELL,
+                egui::Vec2::splat(LOUPE_CELL),
+            );
+            let [r, g, b, _] = rgba.map(component_byte);
+            painter.rect_filled(cell, 0.0, egui::Color32::from_rgb(r, g, b));
+        }
+
+        let centre = egui::Rect::from_min_size(
+            min + egui::Vec2::splat(LOUPE_RADIUS as f32 * LOUPE_CELL),
+            egui::Vec2::splat(LOUPE_CELL),
+        );
+        painter.rect_stroke(centre, 0.0, egui::Stroke::new(1.0, egui::Color32::WHITE));
+        painter.rect_stroke(centre.expand(1.0), 0.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
+
+        painter.text(
+            egui::pos2(frame.left() + 2.0, frame.bottom() - 2.0),
+            egui::Align2::LEFT_BOTTOM,
+            format!("{} {}, {}", readback.loupe_stage, readback.pixel[0], readback.pixel[1]),
+            egui::FontId::monospace(10.0),
+            egui::Color32::WHITE,
+        );
+    }
+}
+
+// ==========================================
+// COPY TO UNIFORM
+// ==========================================
+
+impl ShaderApp {
+    /// Color uniforms of every pass as (pass, uniform), main shader first
+    pub fn color_uniform_targets(&self) -> Vec<(String, String)> {
+        let main = std::iter::once((MAIN_PASS_NAME, &self.uniforms));
+        let passes = self.buffers.iter()
+            .chain(&self.post_stages)
+            .filter(|pass| pass.is_active())
+            .map(|pass| (pass.name.as_str(), &pass.uniforms));
+
+        let mut targets = Vec::new();
+        for (pass, uniforms) in main.chain(passes) {
+            let mut names: Vec<&String> = uniforms.iter()
+                .filter(|(_, info)| info.meta.color.is_some() && matches!(info.uniform_type, UniformType::Vec3 | UniformType::Vec4))
+                .map(|(name, _)| name)
+                .collect();
+            names.sort();
+            targets.extend(names.into_iter().map(|name| (pass.to_string(), name.clone())));
+        }
+        targets
+    }
+
+    /// Set a color uniform so it uploads exactly the inspected pixel value
+    pub fn copy_color_to_uniform(&mut self, pass: &str, name: &str, rgba: [f32; 4]) {
+        let uniforms = if pass == MAIN_PASS_NAME {
+            Some(&mut self.uniforms)
+        } else {
+            self.buffers.iter_mut()
+                .chain(&mut self.post_stages)
+                .find(|stage| stage.name == pass)
+                .map(|stage| &mut stage.uniforms)
+        };
+        match uniforms.and_then(|uniforms| uniforms.get_mut(name)) {
+            Some(info) => info.set_color(rgba),
+            None => log::error!("Color uniform {} of {} no longer exists", name, pass),
+        }
+    }
+}
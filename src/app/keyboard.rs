@@ -0,0 +1,204 @@
+use super::data::{TextureFilter, TextureHandle, TextureParams, TextureWrap};
+
+use std::path::PathBuf;
+use glow::HasContext;
+
+/// Sampler uniform that receives the keyboard texture
+pub const KEYBOARD_UNIFORM: &str = "u_keyboard";
+
+const KEY_COUNT: usize = 256;
+
+// ==========================================
+// KEYBOARD STATE
+// ==========================================
+
+/// Shadertoy-style keyboard texture: 256x3, one texel per JavaScript key code.
+/// Row 0 holds keys that are down, row 1 keys pressed this frame, row 2 toggle state.
+pub struct KeyboardState {
+    down: [bool; KEY_COUNT],
+    pressed: [bool; KEY_COUNT],
+    toggled: [bool; KEY_COUNT],
+    texture: Option<glow::Texture>,
+}
+
+impl Default for KeyboardState {
+    fn default() -> Self {
+        Self {
+            down: [false; KEY_COUNT],
+            pressed: [false; KEY_COUNT],
+            toggled: [false; KEY_COUNT],
+            texture: None,
+        }
+    }
+}
+
+impl KeyboardState {
+    /// Apply this frame's key events; pass `capture = false` while a text field has focus
+    pub fn update(&mut self, input: &egui::InputState, capture: bool) {
+        self.pressed = [false; KEY_COUNT];
+
+        if !capture {
+            self.down = [false; KEY_COUNT];
+            return;
+        }
+
+        for event in &input.events {
+            if let egui::Event::Key { key, pressed, repeat, .. } = event
+                && let Some(code) = key_code(*key)
+            {
+                self.set_key(code, *pressed, *repeat);
+            }
+        }
+
+        // Modifiers don't produce key events in egui
+        let modifiers = [
+            (16, input.modifiers.shift),
+            (17, input.modifiers.ctrl),
+            (18, input.modifiers.alt),
+        ];
+        for (code, down) in modifiers {
+            if down != self.down[code] {
+                self.set_key(code, down, false);
+            }
+        }
+    }
+
+    fn set_key(&mut self, code: usize, down: bool, repeat: bool) {
+        if down && !repeat && !self.down[code] {
+            self.pressed[code] = true;
+            self.toggled[code] = !self.toggled[code];
+        }
+        self.down[code] = down;
+    }
+
+    /// Upload the current state and return a handle for the sampler uniform
+    pub fn upload(&mut self, gl: &glow::Context) -> Option<TextureHandle> {
+        let mut pixels = Vec::with_capacity(KEY_COUNT * 3);
+        for row in [&self.down, &self.pressed, &self.toggled] {
+            pixels.extend(row.iter().map(|&on| if on { 255u8 } else { 0 }));
+        }
+
+        unsafe {
+            let texture = match self.texture {
+                Some(texture) => texture,
+                None => {
+                    let texture = gl.create_texture().ok()?;
+                    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::NEAREST as i32);
+                    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+                    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+                    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+                    self.texture = Some(texture);
+                    texture
+                }
+            };
+
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_image_2d(
+                glow::TEXTURE_2D, 0, glow::R8 as i32,
+                KEY_COUNT as i32, 3, 0,
+                glow::RED, glow::UNSIGNED_BYTE, Some(&pixels),
+            );
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            Some(TextureHandle {
+                path: PathBuf::from("[keyboard]"),
+                texture_id: Some(texture),
+                width: KEY_COUNT as u32,
+                height: 3,
+                params: TextureParams {
+                    wrap: TextureWrap::Clamp,
+                    filter: TextureFilter::Nearest,
+                    flip_y: false,
+                    ..TextureParams::default()
+                },
+                image: None,
+            })
+        }
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        if let Some(texture) = self.texture.take() {
+            unsafe {
+                gl.delete_texture(texture);
+            }
+        }
+    }
+}
+
+/// JavaScript key code for an egui key, as used by Shadertoy
+fn key_code(key: egui::Key) -> Option<usize> {
+    use egui::Key;
+
+    let code = match key {
+        Key::Backspace => 8,
+        Key::Tab => 9,
+        Key::Enter => 13,
+        Key::Escape => 27,
+        Key::Space => 32,
+        Key::PageUp => 33,
+        Key::PageDown => 34,
+        Key::End => 35,
+        Key::Home => 36,
+        Key::ArrowLeft => 37,
+        Key::ArrowUp => 38,
+        Key::ArrowRight => 39,
+        Key::ArrowDown => 40,
+        Key::Insert => 45,
+        Key::Delete => 46,
+        Key::Num0 => 48,
+        Key::Num1 => 49,
+        Key::Num2 => 50,
+        Key::Num3 => 51,
+        Key::Num4 => 52,
+        Key::Num5 => 53,
+        Key::Num6 => 54,
+        Key::Num7 => 55,
+        Key::Num8 => 56,
+        Key::Num9 => 57,
+        Key::A => 65,
+        Key::B => 66,
+        Key::C => 67,
+        Key::D => 68,
+        Key::E => 69,
+        Key::F => 70,
+        Key::G => 71,
+        Key::H => 72,
+        Key::I => 73,
+        Key::J => 74,
+        Key::K => 75,
+        Key::L => 76,
+        Key::M => 77,
+        Key::N => 78,
+        Key::O => 79,
+        Key::P => 80,
+        Key::Q => 81,
+        Key::R => 82,
+        Key::S => 83,
+        Key::T => 84,
+        Key::U => 85,
+        Key::V => 86,
+        Key::W => 87,
+        Key::X => 88,
+        Key::Y => 89,
+        Key::Z => 90,
+        Key::F1 => 112,
+        Key::F2 => 113,
+        Key::F3 => 114,
+        Key::F4 => 115,
+        Key::F5 => 116,
+        Key::F6 => 117,
+        Key::F7 => 118,
+        Key::F8 => 119,
+        Key::F9 => 120,
+        Key::F10 => 121,
+        Key::F11 => 122,
+        Key::F12 => 123,
+        Key::PlusEquals => 187,
+        Key::Minus => 189,
+        _ => return None,
+    };
+    Some(code)
+}
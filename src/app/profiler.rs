@@ -0,0 +1,492 @@
+use crate::app::ShaderApp;
+use super::data::{FrameInputs, TargetFormat};
+use super::file_io::export_frame_inputs;
+use super::render_graph::{PassTargets, RenderTarget};
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use egui::mutex::Mutex;
+use egui_glow::glow;
+use glow::HasContext;
+
+/// Frames kept for the HUD graph and averages
+pub const HUD_HISTORY: usize = 120;
+
+/// Frames averaged for the numbers shown in the HUD
+const HUD_AVERAGE: usize = 30;
+
+/// Frames of queries allowed in flight before the oldest is read back blocking
+const MAX_PENDING_FRAMES: usize = 8;
+
+/// Longest GPU time a timer query can report, its 32-bit nanosecond result wraps around after this
+const MAX_GPU_TIME_MS: f32 = u32::MAX as f32 / 1_000_000.0;
+
+/// Time spent on benchmark frames per UI frame
+const BENCHMARK_BUDGET: Duration = Duration::from_millis(100);
+
+// ==========================================
+// GPU TIMER QUERIES
+// ==========================================
+
+/// Rolling CPU and GPU timings. GPU time per pass comes from `GL_TIME_ELAPSED` queries,
+/// read back a few frames late so the CPU never waits for the GPU.
+pub struct Profiler {
+    history_len: usize,
+    /// Timer queries are available, checked on first use
+    supported: Option<bool>,
+    free: Vec<glow::Query>,
+    /// Queries of the frame being recorded
+    current: Vec<(String, glow::Query)>,
+    /// Recorded frames waiting for their results, oldest first
+    pending: VecDeque<Vec<(String, glow::Query)>>,
+    /// Milliseconds per pass of the latest finished frames, oldest first
+    pub gpu_frames: VecDeque<Vec<(String, f32)>>,
+    /// Milliseconds the app spent building each frame
+    pub cpu_frames: VecDeque<f32>,
+    /// Milliseconds between frames
+    pub intervals: VecDeque<f32>,
+}
+
+impl Profiler {
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            history_len,
+            supported: None,
+            free: Vec::new(),
+            current: Vec::new(),
+            pending: VecDeque::new(),
+            gpu_frames: VecDeque::new(),
+            cpu_frames: VecDeque::new(),
+            intervals: VecDeque::new(),
+        }
+    }
+
+    fn is_supported(&mut self, gl: &glow::Context) -> bool {
+        *self.supported.get_or_insert_with(|| {
+            let version = gl.version();
+            let extensions = gl.supported_extensions();
+            let supported = (!version.is_embedded && (version.major, version.minor) >= (3, 3))
+                || extensions.contains("GL_ARB_timer_query")
+                || extensions.contains("GL_EXT_disjoint_timer_query");
+            if !supported {
+                log::warn!("GPU timer queries are not supported, GPU times won't be shown");
+            }
+            supported
+        })
+    }
+
+    /// Collect every recorded frame whose results have arrived
+    pub fn begin_frame(&mut self, gl: &glow::Context) {
+        while let Some(frame) = self.pending.front() {
+            let blocking = self.pending.len() > MAX_PENDING_FRAMES;
+            let available = frame.iter().all(|(_, query)| unsafe {
+                gl.get_query_parameter_u32(*query, glow::QUERY_RESULT_AVAILABLE) != 0
+            });
+            if !available && !blocking {
+                break;
+            }
+            if let Some(frame) = self.pending.pop_front() {
+                self.collect(gl, frame);
+            }
+        }
+    }
+
+    /// Wait for every recorded frame, for benchmarks
+    pub fn finish(&mut self, gl: &glow::Context) {
+        while let Some(frame) = self.pending.pop_front() {
+            self.collect(gl, frame);
+        }
+    }
+
+    /// Results are read as 32 bits, glow has no 64-bit query getter, so a pass over
+    /// `MAX_GPU_TIME_MS` (about 4.3 s) reports its time modulo that
+    fn collect(&mut self, gl: &glow::Context, frame: Vec<(String, glow::Query)>) {
+        let mut passes = Vec::with_capacity(frame.len());
+        for (name, query) in frame {
+            let nanoseconds = unsafe { gl.get_query_parameter_u32(query, glow::QUERY_RESULT) };
+            passes.push((name, nanoseconds as f32 / 1_000_000.0));
+            self.free.push(query);
+        }
+        push_capped(&mut self.gpu_frames, passes, self.history_len);
+    }
+
+    /// Time the GL commands issued by `draw`
+    pub fn time_pass(&mut self, gl: &glow::Context, name: &str, draw: impl FnOnce()) {
+        if !self.is_supported(gl) {
+            draw();
+            return;
+        }
+        let query = match self.free.pop() {
+            Some(query) => query,
+            None => match unsafe { gl.create_query() } {
+                Ok(query) => query,
+                Err(e) => {
+                    log::error!("Failed to create timer query: {}", e);
+                    self.supported = Some(false);
+                    draw();
+                    return;
+                }
+            },
+        };
+
+        unsafe {
+            gl.begin_query(glow::TIME_ELAPSED, query);
+        }
+        draw();
+        unsafe {
+            gl.end_query(glow::TIME_ELAPSED);
+        }
+        self.current.push((name.to_string(), query));
+    }
+
+    pub fn end_frame(&mut self) {
+        if !self.current.is_empty() {
+            self.pending.push_back(std::mem::take(&mut self.current));
+        }
+    }
+
+    /// CPU time spent on a frame and the time since the previous one
+    pub fn record_cpu(&mut self, cpu: Duration, interval: f32) {
+        let history_len = self.history_len;
+        push_capped(&mut self.cpu_frames, cpu.as_secs_f32() * 1000.0, history_len);
+        push_capped(&mut self.intervals, interval * 1000.0, history_len);
+    }
+
+    /// Drop all timings, e.g. after the HUD was hidden for a while
+    pub fn reset(&mut self, gl: &glow::Context) {
+        self.finish(gl);
+        self.gpu_frames.clear();
+        self.cpu_frames.clear();
+        self.intervals.clear();
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        let queries = self.free.drain(..)
+            .chain(self.current.drain(..).map(|(_, query)| query))
+            .chain(self.pending.drain(..).flatten().map(|(_, query)| query));
+        for query in queries {
+            unsafe {
+                gl.delete_query(query);
+            }
+        }
+    }
+
+    /// GPU milliseconds of a whole frame, for the latest finished frames
+    fn gpu_totals(&self) -> impl Iterator<Item = f32> + '_ {
+        self.gpu_frames.iter().map(|passes| passes.iter().map(|(_, ms)| ms).sum())
+    }
+
+    /// Average GPU milliseconds per pass over the last `count` frames, in pass order
+    fn pass_averages(&self, count: usize) -> Vec<(String, f32)> {
+        let frames: Vec<&Vec<(String, f32)>> = self.gpu_frames.iter().rev().take(count).collect();
+        let Some(latest) = frames.first() else {
+            return Vec::new();
+        };
+        latest.iter()
+            .map(|(name, _)| {
+                let times: Vec<f32> = frames.iter()
+                    .filter_map(|passes| passes.iter().find(|(pass, _)| pass == name))
+                    .map(|(_, ms)| *ms)
+                    .collect();
+                (name.clone(), times.iter().sum::<f32>() / times.len() as f32)
+            })
+            .collect()
+    }
+
+    // ==========================================
+    // HUD
+    // ==========================================
+
+    /// Overlay with FPS, CPU time, GPU time per pass and a graph of recent frames
+    pub fn paint_hud(&self, painter: &egui::Painter, rect: egui::Rect) {
+        let interval = recent_average(self.intervals.iter().copied());
+        let mut lines = vec![
+            format!("FPS {:.0} ({:.1} ms)", if interval > 0.0 { 1000.0 / interval } else { 0.0 }, interval),
+            format!("CPU {:.2} ms", recent_average(self.cpu_frames.iter().copied())),
+        ];
+        let passes = self.pass_averages(HUD_AVERAGE);
+        if passes.is_empty() {
+            lines.push("GPU n/a".to_string());
+        } else {
+            lines.push(format!("GPU {:.2} ms", passes.iter().map(|(_, ms)| ms).sum::<f32>()));
+            lines.extend(passes.iter().map(|(name, ms)| format!("  {} {:.2} ms", name, ms)));
+        }
+
+        let font = egui::FontId::monospace(11.0);
+        let line_height = 14.0;
+        let graph_height = 40.0;
+        let width = 180.0;
+        let origin = rect.left_top() + egui::vec2(8.0, 8.0);
+        let text_height = lines.len() as f32 * line_height;
+        let hud = egui::Rect::from_min_size(origin, egui::vec2(width, text_height + graph_height + 12.0));
+
+        painter.rect_filled(hud, 4.0, egui::Color32::from_black_alpha(180));
+        for (index, line) in lines.iter().enumerate() {
+            painter.text(
+                origin + egui::vec2(6.0, 4.0 + index as f32 * line_height),
+                egui::Align2::LEFT_TOP,
+                line,
+                font.clone(),
+                egui::Color32::WHITE,
+            );
+        }
+
+        // Frame interval and GPU time, scaled so 33 ms (30 FPS) fills the graph
+        let graph = egui::Rect::from_min_size(
+            egui::pos2(hud.left() + 6.0, hud.bottom() - graph_height - 6.0),
+            egui::vec2(width - 12.0, graph_height),
+        );
+        painter.rect_stroke(graph, 0.0, egui::Stroke::new(1.0, egui::Color32::from_gray(80)));
+        let scale = 33.3f32.max(self.intervals.iter().copied().fold(0.0, f32::max));
+        let plot = |values: Vec<f32>, color: egui::Color32| {
+            let step = graph.width() / (HUD_HISTORY - 1) as f32;
+            let offset = HUD_HISTORY.saturating_sub(values.len()) as f32 * step;
+            let points: Vec<egui::Pos2> = values.iter()
+                .enumerate()
+                .map(|(index, ms)| egui::pos2(
+                    graph.left() + offset + index as f32 * step,
+                    graph.bottom() - (ms / scale).min(1.0) * graph.height(),
+                ))
+                .collect();
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+        };
+        plot(self.intervals.iter().copied().collect(), egui::Color32::LIGHT_GREEN);
+        plot(self.gpu_totals().collect(), egui::Color32::LIGHT_RED);
+    }
+}
+
+/// Shared handle the render passes report their draws to; does nothing when timing is off
+#[derive(Clone, Default)]
+pub struct PassTimer(Option<Arc<Mutex<Profiler>>>);
+
+impl PassTimer {
+    pub fn new(profiler: &Arc<Mutex<Profiler>>) -> Self {
+        Self(Some(profiler.clone()))
+    }
+
+    pub fn begin_frame(&self, gl: &glow::Context) {
+        if let Some(profiler) = &self.0 {
+            profiler.lock().begin_frame(gl);
+        }
+    }
+
+    /// Run `draw` inside a timer query labelled with the pass name
+    pub fn time(&self, gl: &glow::Context, name: &str, draw: impl FnOnce()) {
+        match &self.0 {
+            Some(profiler) => profiler.lock().time_pass(gl, name, draw),
+            None => draw(),
+        }
+    }
+
+    pub fn end_frame(&self) {
+        if let Some(profiler) = &self.0 {
+            profiler.lock().end_frame();
+        }
+    }
+}
+
+fn push_capped<T>(values: &mut VecDeque<T>, value: T, capacity: usize) {
+    if values.len() == capacity {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+/// Average of the most recent values
+fn recent_average(values: impl DoubleEndedIterator<Item = f32>) -> f32 {
+    let recent: Vec<f32> = values.rev().take(HUD_AVERAGE).collect();
+    if recent.is_empty() {
+        0.0
+    } else {
+        recent.iter().sum::<f32>() / recent.len() as f32
+    }
+}
+
+// ==========================================
+// BENCHMARK
+// ==========================================
+
+/// Min, average and max of a series of timings in milliseconds
+#[derive(Debug, Clone, Copy)]
+pub struct TimingStats {
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+}
+
+impl TimingStats {
+    fn from_samples(samples: &[f32]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        Some(Self {
+            min: samples.iter().copied().fold(f32::INFINITY, f32::min),
+            avg: samples.iter().sum::<f32>() / samples.len() as f32,
+            max: samples.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        })
+    }
+}
+
+impl std::fmt::Display for TimingStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2} / {:.2} / {:.2} ms", self.min, self.avg, self.max)
+    }
+}
+
+/// Timings of a benchmark run at export resolution
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Wall time per frame, waiting for the GPU to finish
+    pub frame: TimingStats,
+    /// GPU time per pass
+    pub passes: Vec<(String, TimingStats)>,
+    /// Some frame took longer than a timer query can count, so pass times may be too low
+    pub gpu_wrapped: bool,
+}
+
+/// A benchmark in progress, rendering a few frames per UI frame so the window stays responsive
+pub struct BenchmarkRun {
+    width: u32,
+    height: u32,
+    frames: u32,
+    output: RenderTarget,
+    targets: PassTargets,
+    profiler: Arc<Mutex<Profiler>>,
+    start_inputs: FrameInputs,
+    /// Wall time of every rendered frame in milliseconds
+    frame_times: Vec<f32>,
+}
+
+impl BenchmarkRun {
+    /// Rendered and total frames
+    pub fn progress(&self) -> (u32, u32) {
+        (self.frame_times.len() as u32, self.frames)
+    }
+
+    fn destroy(mut self, gl: &glow::Context) {
+        self.profiler.lock().destroy(gl);
+        self.targets.destroy(gl);
+        self.output.destroy(gl);
+    }
+}
+
+impl ShaderApp {
+    /// Start rendering `benchmark_frames` frames at export resolution, timed by `poll_benchmark`
+    pub fn start_benchmark(&mut self) {
+        let [width, height] = self.export_resolution;
+        let frames = self.benchmark_frames;
+        if width > self.max_texture_size || height > self.max_texture_size {
+            log::error!("Benchmark resolution {}x{} exceeds the GPU limit of {}px", width, height, self.max_texture_size);
+            return;
+        }
+        log::info!("Benchmarking {} frames at {}x{}", frames, width, height);
+
+        let output = match RenderTarget::new(&self.gl, width, height, TargetFormat::Rgba8) {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("Failed to create benchmark framebuffer: {}", e);
+                return;
+            }
+        };
+        // Fresh targets so feedback starts from black, like an export
+        let mut targets = PassTargets::default();
+        targets.ensure(&self.gl, &self.target_specs(), width, height);
+        self.benchmark_run = Some(BenchmarkRun {
+            width,
+            height,
+            frames,
+            output,
+            targets,
+            profiler: Arc::new(Mutex::new(Profiler::new(frames as usize))),
+            start_inputs: self.export_inputs(egui::Vec2::new(width as f32, height as f32)),
+            frame_times: Vec::with_capacity(frames as usize),
+        });
+    }
+
+    /// Render the next benchmark frames, waiting for the GPU after each, and report once all are done
+    pub fn poll_benchmark(&mut self) {
+        let Some(mut run) = self.benchmark_run.take() else {
+            return;
+        };
+
+        let gl = self.gl.clone();
+        let viewport = [0, 0, run.width as i32, run.height as i32];
+        let slice_start = Instant::now();
+        while run.frame_times.len() < run.frames as usize && slice_start.elapsed() < BENCHMARK_BUDGET {
+            let frame = run.frame_times.len() as u32;
+            let inputs = export_frame_inputs(&run.start_inputs, frame, self.video_fps);
+            let started = Instant::now();
+            run.targets.swap();
+            let mut plan = self.frame_plan(&run.targets);
+            plan.timer = PassTimer::new(&run.profiler);
+            unsafe {
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(run.output.fbo));
+                gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+            }
+            plan.render(&gl, &inputs, Some(run.output.fbo), viewport);
+            unsafe {
+                gl.finish();
+                gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            }
+            run.frame_times.push(started.elapsed().as_secs_f32() * 1000.0);
+        }
+        if run.frame_times.len() < run.frames as usize {
+            self.benchmark_run = Some(run);
+            return;
+        }
+
+        let mut pass_times: Vec<(String, Vec<f32>)> = Vec::new();
+        {
+            let mut profiler = run.profiler.lock();
+            profiler.finish(&gl);
+            for passes in &profiler.gpu_frames {
+                for (name, ms) in passes {
+                    match pass_times.iter_mut().find(|(pass, _)| pass == name) {
+                        Some((_, times)) => times.push(*ms),
+                        None => pass_times.push((name.clone(), vec![*ms])),
+                    }
+                }
+            }
+        }
+        let (width, height, frames) = (run.width, run.height, run.frames);
+        let frame_times = std::mem::take(&mut run.frame_times);
+        run.destroy(&gl);
+
+        let Some(frame) = TimingStats::from_samples(&frame_times) else {
+            return;
+        };
+        // A pass can't take longer than its frame, so only frames this slow can wrap
+        let gpu_wrapped = frame.max >= MAX_GPU_TIME_MS;
+        let result = BenchmarkResult {
+            frames,
+            width,
+            height,
+            frame,
+            passes: pass_times.iter()
+                .filter_map(|(name, times)| Some((name.clone(), TimingStats::from_samples(times)?)))
+                .collect(),
+            gpu_wrapped,
+        };
+        log::info!("Benchmark {}x{}, {} frames: {} (min / avg / max)", width, height, frames, result.frame);
+        for (name, stats) in &result.passes {
+            log::info!("  {}: {}", name, stats);
+        }
+        if gpu_wrapped {
+            log::warn!("Frames took over {:.0} ms, GPU times may have wrapped around", MAX_GPU_TIME_MS);
+        }
+        self.benchmark_result = Some(result);
+    }
+
+    pub fn cancel_benchmark(&mut self) {
+        if let Some(run) = self.benchmark_run.take() {
+            run.destroy(&self.gl);
+            log::info!("Benchmark cancelled");
+        }
+    }
+}
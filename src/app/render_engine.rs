@@ -5,6 +5,26 @@ use egui_glow::glow;
 pub struct ShaderRenderer {
     program: glow::Program,
     vertex_array: glow::VertexArray,
+    /// Uniforms the linked program uses, queried once at link time
+    active_uniforms: HashMap<String, ActiveUniform>,
+    samplers: Samplers,
+}
+
+/// A sampler object for every wrap, filter and mipmap combination. Each sampler uniform reads
+/// through the one matching its texture handle's settings, so passes can sample the same
+/// texture differently.
+struct Samplers(HashMap<(TextureWrap, TextureFilter, bool), glow::Sampler>);
+
+/// A uniform the driver kept in the linked program
+#[derive(Clone)]
+pub struct ActiveUniform {
+    pub location: glow::UniformLocation,
+    /// GL type enum, e.g. `glow::FLOAT_VEC3`
+    pub gl_type: u32,
+    /// Array length, 1 for plain uniforms
+    pub size: i32,
+    /// Reported as `name[0]`; an array whose first element alone is used has size 1
+    pub is_array: bool,
 }
 
 impl ShaderRenderer {
@@ -13,14 +33,25 @@ impl ShaderRenderer {
 
         let shader_version = egui_glow::ShaderVersion::get(gl);
 
+        let samplers = Samplers::new(gl)?;
+
         // SAFETY: All OpenGL calls are made with a valid context.
         // Error handling ensures resources are cleaned up on failure.
         unsafe {
-            let program = gl.create_program().map_err(|e| e.to_string())?;
+            let program = match gl.create_program() {
+                Ok(program) => program,
+                Err(e) => {
+                    samplers.destroy(gl);
+                    return Err(e);
+                }
+            };
 
             let vertex_shader_source = r#"
                 out vec2 v_uv;
                 
+                // Part of the full frame's UVs this draw covers, for tiled rendering
+                uniform vec4 u_uvRect;
+                
                 const vec2 verts[4] = vec2[4](
                     vec2(-1.0, -1.0), vec2(1.0, -1.0),
                     vec2(-1.0, 1.0),  vec2(1.0, 1.0)
@@ -32,7 +63,7 @@ impl ShaderRenderer {
                 );
                 
                 void main() {
-                    v_uv = uvs[gl_VertexID];
+                    v_uv = u_uvRect.xy + uvs[gl_VertexID] * u_uvRect.zw;
                     gl_Position = vec4(verts[gl_VertexID], 0.0, 1.0);
                 }
             "#;
@@ -45,7 +76,18 @@ impl ShaderRenderer {
             let mut shaders = Vec::with_capacity(shader_sources.len());
 
             for (shader_type, shader_source) in shader_sources.iter() {
-                let shader = gl.create_shader(*shader_type).map_err(|e| e.to_string())?;
+                let shader = match gl.create_shader(*shader_type) {
+                    Ok(shader) => shader,
+                    Err(e) => {
+                        for prev_shader in shaders {
+                            gl.detach_shader(program, prev_shader);
+                            gl.delete_shader(prev_shader);
+                        }
+                        gl.delete_program(program);
+                        samplers.destroy(gl);
+                        return Err(e);
+                    }
+                };
                 
                 let source_with_version = if *shader_type == glow::FRAGMENT_SHADER {
                     shader_source.to_string()
@@ -65,6 +107,7 @@ impl ShaderRenderer {
                         gl.delete_shader(prev_shader);
                     }
                     gl.delete_program(program);
+                    samplers.destroy(gl);
                     return Err(info_log);
                 }
                 
@@ -80,6 +123,7 @@ impl ShaderRenderer {
                     gl.delete_shader(shader);
                 }
                 gl.delete_program(program);
+                samplers.destroy(gl);
                 return Err(info_log);
             }
 
@@ -89,9 +133,17 @@ impl ShaderRenderer {
                 gl.delete_shader(shader);
             }
 
-            let vertex_array = gl.create_vertex_array().map_err(|e| e.to_string())?;
+            let vertex_array = match gl.create_vertex_array() {
+                Ok(vertex_array) => vertex_array,
+                Err(e) => {
+                    gl.delete_program(program);
+                    samplers.destroy(gl);
+                    return Err(e);
+                }
+            };
+            let active_uniforms = reflect_uniforms(gl, program);
 
-            Ok(Self { program, vertex_array })
+            Ok(Self { program, vertex_array, active_uniforms, samplers })
         }
     }
 
@@ -103,64 +155,334 @@ impl ShaderRenderer {
             gl.delete_program(self.program);
             gl.delete_vertex_array(self.vertex_array);
         }
+        self.samplers.destroy(gl);
     }
 
-    pub fn paint(&self, gl: &glow::Context, time: f32, size: egui::Vec2, uniforms: &HashMap<String, UniformInfo>) {
+    pub fn paint(&self, gl: &glow::Context, inputs: &FrameInputs, uniforms: &HashMap<String, UniformInfo>) {
         use glow::HasContext as _;
         // SAFETY: Rendering with a valid OpenGL context and program.
         // All uniform locations are queried before use.
         unsafe {
             gl.use_program(Some(self.program));
             
+            let size = inputs.resolution;
+            let location = |name: &str| self.active_uniforms.get(name).map(|uniform| &uniform.location);
+            
             // Set built-in uniforms
-            if let Some(loc) = gl.get_uniform_location(self.program, "u_time") {
-                gl.uniform_1_f32(Some(&loc), time);
+            if let Some(loc) = location("u_time") {
+                gl.uniform_1_f32(Some(loc), inputs.time);
+            }
+            if let Some(loc) = location("u_resolution") {
+                gl.uniform_2_f32(Some(loc), size.x, size.y);
+            }
+            if let Some(loc) = location("u_frame") {
+                gl.uniform_1_i32(Some(loc), inputs.frame as i32);
+            }
+            if let Some(loc) = location("u_timeDelta") {
+                gl.uniform_1_f32(Some(loc), inputs.time_delta);
+            }
+            if let Some(loc) = location("u_date") {
+                let [year, month, day, seconds] = inputs.date;
+                gl.uniform_4_f32(Some(loc), year, month, day, seconds);
+            }
+            if let Some(loc) = location("u_fps") {
+                gl.uniform_1_f32(Some(loc), inputs.frame_rate);
+            }
+            if let Some(loc) = location("u_tileOffset") {
+                gl.uniform_2_f32(Some(loc), inputs.tile[0], inputs.tile[1]);
+            }
+            if let Some(loc) = location("u_uvRect") {
+                let [x, y, w, h] = inputs.uv_rect();
+                gl.uniform_4_f32(Some(loc), x, y, w, h);
+            }
+            if let Some(loc) = location("u_mouse") {
+                let [x, y, z, w] = inputs.mouse.to_hover_vec4();
+                gl.uniform_4_f32(Some(loc), x, y, z, w);
+            }
+            
+            // Shadertoy built-ins
+            if let Some(loc) = location("iResolution") {
+                gl.uniform_3_f32(Some(loc), size.x, size.y, 1.0);
+            }
+            if let Some(loc) = location("iTime") {
+                gl.uniform_1_f32(Some(loc), inputs.time);
+            }
+            if let Some(loc) = location("iTimeDelta") {
+                gl.uniform_1_f32(Some(loc), inputs.time_delta);
+            }
+            if let Some(loc) = location("iFrameRate") {
+                gl.uniform_1_f32(Some(loc), inputs.frame_rate);
+            }
+            if let Some(loc) = location("iFrame") {
+                gl.uniform_1_i32(Some(loc), inputs.frame as i32);
             }
-            if let Some(loc) = gl.get_uniform_location(self.program, "u_resolution") {
-                gl.uniform_2_f32(Some(&loc), size.x, size.y);
+            if let Some(loc) = location("iMouse") {
+                let [x, y, z, w] = inputs.mouse.to_vec4();
+                gl.uniform_4_f32(Some(loc), x, y, z, w);
+            }
+            if let Some(loc) = location("iDate") {
+                let [year, month, day, seconds] = inputs.date;
+                gl.uniform_4_f32(Some(loc), year, month, day, seconds);
+            }
+            if let Some(loc) = location("iChannelResolution") {
+                let mut channel_resolution = [0.0; 12];
+                for (channel, resolution) in channel_resolution.chunks_mut(3).enumerate() {
+                    if let Some(UniformValue::Sampler2D(Some(handle))) =
+                        uniforms.get(&format!("iChannel{}", channel)).map(|u| &u.value)
+                    {
+                        resolution.copy_from_slice(&[handle.width as f32, handle.height as f32, 1.0]);
+                    }
+                }
+                gl.uniform_3_f32_slice(Some(loc), &channel_resolution);
             }
 
             let mut texture_unit = 0;
             
             // Set custom uniforms
             for (name, uniform_info) in uniforms {
-                if name == "u_resolution" || name == "u_time" {
+                if is_builtin_uniform(name) {
                     continue;
                 }
-                if let Some(loc) = gl.get_uniform_location(self.program, name) {
-                    match &uniform_info.value {
-                        UniformValue::Float(val) => {
-                            gl.uniform_1_f32(Some(&loc), *val);
-                        }
-                        UniformValue::Vec2(vals) => {
-                            gl.uniform_2_f32(Some(&loc), vals[0], vals[1]);
-                        }
-                        UniformValue::Vec3(vals) => {
-                            gl.uniform_3_f32(Some(&loc), vals[0], vals[1], vals[2]);
-                        }
-                        UniformValue::Vec4(vals) => {
-                            gl.uniform_4_f32(Some(&loc), vals[0], vals[1], vals[2], vals[3]);
-                        }
-                        UniformValue::Sampler2D(texture_handle) => {
-                            if let Some(handle) = texture_handle {
-                                if let Some(texture_id) = handle.texture_id {
-                                    // Activate texture unit
-                                    gl.active_texture(glow::TEXTURE0 + texture_unit);
-                                    gl.bind_texture(glow::TEXTURE_2D, Some(texture_id));
-                                    
-                                    // Set uniform to texture unit
-                                    gl.uniform_1_i32(Some(&loc), texture_unit as i32);
-                                    
-                                    texture_unit += 1;
-                                }
-                            }
-                        }
-                    }
+                if let Some(loc) = location(name) {
+                    upload_value(gl, loc, &uniform_info.upload_value(), &self.samplers, &mut texture_unit);
                 }
             }
             
             gl.bind_vertex_array(Some(self.vertex_array));
             gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+
+            // egui samples its textures with their own parameters
+            for unit in 0..texture_unit {
+                gl.bind_sampler(unit, None);
+            }
+        }
+    }
+
+    /// Uniforms the driver reports as used, by name
+    pub fn active_uniforms(&self) -> &HashMap<String, ActiveUniform> {
+        &self.active_uniforms
+    }
+}
+
+impl ActiveUniform {
+    /// Editor type of the uniform, `None` for types the editor doesn't support
+    pub fn uniform_type(&self) -> Option<UniformType> {
+        UniformType::from_gl(self.gl_type, self.is_array.then_some(self.size as usize))
+    }
+}
+
+impl Samplers {
+    fn new(gl: &glow::Context) -> Result<Self, String> {
+        use glow::HasContext as _;
+        let mut samplers = Self(HashMap::new());
+        // SAFETY: Creating sampler objects with a valid context.
+        unsafe {
+            for wrap in TextureWrap::ALL {
+                for filter in TextureFilter::ALL {
+                    for mipmaps in [false, true] {
+                        let sampler = match gl.create_sampler() {
+                            Ok(sampler) => sampler,
+                            Err(e) => {
+                                // Don't leak the samplers created so far
+                                samplers.destroy(gl);
+                                return Err(e);
+                            }
+                        };
+                        gl.sampler_parameter_i32(sampler, glow::TEXTURE_MIN_FILTER, filter.gl_min_filter(mipmaps) as i32);
+                        gl.sampler_parameter_i32(sampler, glow::TEXTURE_MAG_FILTER, filter.gl_mag_filter() as i32);
+                        gl.sampler_parameter_i32(sampler, glow::TEXTURE_WRAP_S, wrap.gl_wrap() as i32);
+                        gl.sampler_parameter_i32(sampler, glow::TEXTURE_WRAP_T, wrap.gl_wrap() as i32);
+                        samplers.0.insert((wrap, filter, mipmaps), sampler);
+                    }
+                }
+            }
+        }
+        Ok(samplers)
+    }
+
+    /// Bind a texture and the sampler for its settings to the next texture unit, returning the unit
+    fn bind(&self, gl: &glow::Context, handle: Option<&TextureHandle>, texture_unit: &mut u32) -> i32 {
+        use glow::HasContext as _;
+        let unit = *texture_unit;
+        let sampler = handle.and_then(|handle| {
+            let params = handle.params;
+            self.0.get(&(params.wrap, params.filter, params.mipmaps)).copied()
+        });
+        // SAFETY: Called from `paint` with a valid context.
+        unsafe {
+            gl.active_texture(glow::TEXTURE0 + unit);
+            gl.bind_texture(glow::TEXTURE_2D, handle.and_then(|handle| handle.texture_id));
+            gl.bind_sampler(unit, sampler);
+        }
+        *texture_unit += 1;
+        unit as i32
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        use glow::HasContext as _;
+        // SAFETY: Deleting samplers created with the same context.
+        unsafe {
+            for sampler in self.0.values() {
+                gl.delete_sampler(*sampler);
+            }
+        }
+    }
+}
+
+/// Query every active uniform of a linked program with its location
+fn reflect_uniforms(gl: &glow::Context, program: glow::Program) -> HashMap<String, ActiveUniform> {
+    use glow::HasContext as _;
+    // SAFETY: The program was linked successfully with this context.
+    unsafe {
+        (0..gl.get_active_uniforms(program))
+            .filter_map(|index| {
+                let uniform = gl.get_active_uniform(program, index)?;
+                // Arrays are reported as `name[0]`
+                let array_name = uniform.name.strip_suffix("[0]");
+                let name = array_name.unwrap_or(&uniform.name).to_string();
+                // Block members have no location and can't be set individually
+                let location = gl.get_uniform_location(program, &name)?;
+                Some((name, ActiveUniform {
+                    location,
+                    gl_type: uniform.utype,
+                    size: uniform.size,
+                    is_array: array_name.is_some(),
+                }))
+            })
+            .collect()
+    }
+}
+
+/// Upload a single uniform value, binding textures to consecutive units
+fn upload_value(gl: &glow::Context, loc: &glow::UniformLocation, value: &UniformValue, samplers: &Samplers, texture_unit: &mut u32) {
+    use glow::HasContext as _;
+    // SAFETY: Called from `paint` with the program bound and a valid location.
+    unsafe {
+        match value {
+            UniformValue::Float(val) => {
+                gl.uniform_1_f32(Some(loc), *val);
+            }
+            UniformValue::Vec2(vals) => {
+                gl.uniform_2_f32(Some(loc), vals[0], vals[1]);
+            }
+            UniformValue::Vec3(vals) => {
+                gl.uniform_3_f32(Some(loc), vals[0], vals[1], vals[2]);
+            }
+            UniformValue::Vec4(vals) => {
+                gl.uniform_4_f32(Some(loc), vals[0], vals[1], vals[2], vals[3]);
+            }
+            UniformValue::Int(val) => {
+                gl.uniform_1_i32(Some(loc), *val);
+            }
+            UniformValue::IVec2(vals) => {
+                gl.uniform_2_i32(Some(loc), vals[0], vals[1]);
+            }
+            UniformValue::IVec3(vals) => {
+                gl.uniform_3_i32(Some(loc), vals[0], vals[1], vals[2]);
+            }
+            UniformValue::IVec4(vals) => {
+                gl.uniform_4_i32(Some(loc), vals[0], vals[1], vals[2], vals[3]);
+            }
+            UniformValue::UInt(val) => {
+                gl.uniform_1_u32(Some(loc), *val);
+            }
+            UniformValue::UVec2(vals) => {
+                gl.uniform_2_u32(Some(loc), vals[0], vals[1]);
+            }
+            UniformValue::UVec3(vals) => {
+                gl.uniform_3_u32(Some(loc), vals[0], vals[1], vals[2]);
+            }
+            UniformValue::UVec4(vals) => {
+                gl.uniform_4_u32(Some(loc), vals[0], vals[1], vals[2], vals[3]);
+            }
+            // Booleans are set through the integer entry points
+            UniformValue::Bool(val) => {
+                gl.uniform_1_i32(Some(loc), *val as i32);
+            }
+            UniformValue::BVec2(vals) => {
+                gl.uniform_2_i32(Some(loc), vals[0] as i32, vals[1] as i32);
+            }
+            UniformValue::BVec3(vals) => {
+                gl.uniform_3_i32(Some(loc), vals[0] as i32, vals[1] as i32, vals[2] as i32);
+            }
+            UniformValue::BVec4(vals) => {
+                gl.uniform_4_i32(Some(loc), vals[0] as i32, vals[1] as i32, vals[2] as i32, vals[3] as i32);
+            }
+            UniformValue::Mat2(vals) => {
+                gl.uniform_matrix_2_f32_slice(Some(loc), false, vals);
+            }
+            UniformValue::Mat3(vals) => {
+                gl.uniform_matrix_3_f32_slice(Some(loc), false, vals);
+            }
+            UniformValue::Mat4(vals) => {
+                gl.uniform_matrix_4_f32_slice(Some(loc), false, vals);
+            }
+            UniformValue::Sampler2D(texture_handle) => {
+                if let Some(handle) = texture_handle
+                    && handle.texture_id.is_some()
+                {
+                    let unit = samplers.bind(gl, Some(handle), texture_unit);
+                    gl.uniform_1_i32(Some(loc), unit);
+                }
+            }
+            UniformValue::Array(elements) => {
+                upload_array(gl, loc, elements, samplers, texture_unit);
+            }
+        }
+    }
+}
+
+/// Upload a uniform array with a single call
+fn upload_array(gl: &glow::Context, loc: &glow::UniformLocation, elements: &[UniformValue], samplers: &Samplers, texture_unit: &mut u32) {
+    use glow::HasContext as _;
+
+    let Some(first) = elements.first() else {
+        return;
+    };
+
+    let floats = || -> Vec<f32> {
+        elements.iter().flat_map(|v| v.float_components().iter().copied()).collect()
+    };
+    let ints = || -> Vec<i32> {
+        elements.iter().flat_map(|v| v.int_components()).collect()
+    };
+    let uints = || -> Vec<u32> {
+        elements.iter().flat_map(|v| v.uint_components().iter().copied()).collect()
+    };
+
+    // SAFETY: Called from `paint` with the program bound and a valid location.
+    unsafe {
+        match first {
+            UniformValue::Float(_) => gl.uniform_1_f32_slice(Some(loc), &floats()),
+            UniformValue::Vec2(_) => gl.uniform_2_f32_slice(Some(loc), &floats()),
+            UniformValue::Vec3(_) => gl.uniform_3_f32_slice(Some(loc), &floats()),
+            UniformValue::Vec4(_) => gl.uniform_4_f32_slice(Some(loc), &floats()),
+            UniformValue::Int(_) | UniformValue::Bool(_) => gl.uniform_1_i32_slice(Some(loc), &ints()),
+            UniformValue::IVec2(_) | UniformValue::BVec2(_) => gl.uniform_2_i32_slice(Some(loc), &ints()),
+            UniformValue::IVec3(_) | UniformValue::BVec3(_) => gl.uniform_3_i32_slice(Some(loc), &ints()),
+            UniformValue::IVec4(_) | UniformValue::BVec4(_) => gl.uniform_4_i32_slice(Some(loc), &ints()),
+            UniformValue::UInt(_) => gl.uniform_1_u32_slice(Some(loc), &uints()),
+            UniformValue::UVec2(_) => gl.uniform_2_u32_slice(Some(loc), &uints()),
+            UniformValue::UVec3(_) => gl.uniform_3_u32_slice(Some(loc), &uints()),
+            UniformValue::UVec4(_) => gl.uniform_4_u32_slice(Some(loc), &uints()),
+            UniformValue::Mat2(_) => gl.uniform_matrix_2_f32_slice(Some(loc), false, &floats()),
+            UniformValue::Mat3(_) => gl.uniform_matrix_3_f32_slice(Some(loc), false, &floats()),
+            UniformValue::Mat4(_) => gl.uniform_matrix_4_f32_slice(Some(loc), false, &floats()),
+            UniformValue::Sampler2D(_) => {
+                // Every element gets its own unit, even when no texture is loaded
+                let units: Vec<i32> = elements.iter()
+                    .map(|element| {
+                        let handle = match element {
+                            UniformValue::Sampler2D(handle) => handle.as_ref(),
+                            _ => None,
+                        };
+                        samplers.bind(gl, handle, texture_unit)
+                    })
+                    .collect();
+                gl.uniform_1_i32_slice(Some(loc), &units);
+            }
+            // GLSL 3.30 has no arrays of arrays
+            UniformValue::Array(_) => {}
         }
     }
 }
@@ -0,0 +1,933 @@
+use crate::app::{ShaderApp, merge_uniform_state, parse_uniforms, prepare_shader_source, reconcile_uniforms};
+use crate::RELOAD_DEBOUNCE_MS;
+use super::data::*;
+use super::profiler::PassTimer;
+use super::render_engine::ShaderRenderer;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use egui::mutex::Mutex;
+use egui_glow::glow;
+use glow::HasContext;
+use notify::{RecommendedWatcher, Watcher};
+
+/// Offscreen buffer passes, sampled by name from any other pass
+pub const BUFFER_NAMES: [&str; 4] = ["Buffer A", "Buffer B", "Buffer C", "Buffer D"];
+
+/// Pass name of the main shader, for feedback targets and sampler inputs
+pub const MAIN_PASS_NAME: &str = "Image";
+
+/// Sampler that reads the pass's own output from the previous frame
+pub const PREV_FRAME_UNIFORM: &str = "u_prevFrame";
+
+/// A pass reads its previous frame through `u_prevFrame` or a sampler bound to itself
+pub fn uses_feedback(pass_name: &str, uniforms: &HashMap<String, UniformInfo>) -> bool {
+    uniforms.iter().any(|(name, info)| {
+        (name == PREV_FRAME_UNIFORM && info.uniform_type == UniformType::Sampler2D)
+            || info.input.as_deref() == Some(pass_name)
+    })
+}
+
+/// Target the main shader draws into when post-process stages follow
+pub const POST_SOURCE_NAME: &str = "Post source";
+
+/// Target holding the final image while it waits for exposure and tonemapping
+pub const OUTPUT_TARGET_NAME: &str = "Output";
+
+/// Preview frame at render resolution, scaled into the canvas
+pub const PREVIEW_TARGET_NAME: &str = "Preview";
+
+/// Sampler through which a post-process stage reads the previous stage
+pub const MAIN_PASS_UNIFORM: &str = "u_mainPass";
+
+/// Pass a sampler reads by naming convention: `u_bufferA` reads "Buffer A"
+pub fn default_input(uniform_name: &str) -> Option<String> {
+    let suffix = uniform_name.strip_prefix("u_buffer")?;
+    BUFFER_NAMES.iter()
+        .find(|name| name.strip_prefix("Buffer ") == Some(suffix))
+        .map(|name| name.to_string())
+}
+
+// ==========================================
+// RENDER TARGETS
+// ==========================================
+
+/// Framebuffer with a single color texture
+pub struct RenderTarget {
+    pub fbo: glow::Framebuffer,
+    pub texture: glow::Texture,
+    pub width: u32,
+    pub height: u32,
+    pub format: TargetFormat,
+}
+
+impl RenderTarget {
+    pub fn new(gl: &glow::Context, width: u32, height: u32, format: TargetFormat) -> Result<Self, String> {
+        let (internal_format, pixel_type) = format.gl_format();
+        unsafe {
+            let texture = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D, 0, internal_format as i32,
+                width as i32, height as i32, 0,
+                glow::RGBA, pixel_type, None,
+            );
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, glow::LINEAR as i32);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, glow::LINEAR as i32);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE as i32);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            let fbo = match gl.create_framebuffer() {
+                Ok(fbo) => fbo,
+                Err(e) => {
+                    gl.delete_texture(texture);
+                    return Err(e);
+                }
+            };
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER, glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D, Some(texture), 0,
+            );
+            let complete = gl.check_framebuffer_status(glow::FRAMEBUFFER) == glow::FRAMEBUFFER_COMPLETE;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            let target = Self { fbo, texture, width, height, format };
+            if !complete {
+                target.destroy(gl);
+                return Err(format!("{} framebuffer {}x{} is incomplete", format.label(), width, height));
+            }
+            // Feedback passes read this before anything was drawn
+            target.clear(gl);
+            Ok(target)
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_framebuffer(self.fbo);
+            gl.delete_texture(self.texture);
+        }
+    }
+
+    pub fn clear(&self, gl: &glow::Context) {
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbo));
+            gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+    }
+
+    /// Handle for binding the color texture to a sampler uniform
+    pub fn texture_handle(&self, label: &str) -> TextureHandle {
+        TextureHandle {
+            path: PathBuf::from(format!("[{}]", label)),
+            texture_id: Some(self.texture),
+            width: self.width,
+            height: self.height,
+            params: TextureParams::PASS_OUTPUT,
+            image: None,
+        }
+    }
+}
+
+/// Where a pass renders, plus the previous frame when it reads its own output
+pub struct PassTarget {
+    current: RenderTarget,
+    previous: Option<RenderTarget>,
+}
+
+impl PassTarget {
+    fn new(gl: &glow::Context, width: u32, height: u32, spec: &TargetSpec) -> Result<Self, String> {
+        let current = RenderTarget::new(gl, width, height, spec.format)?;
+        let previous = if spec.feedback {
+            match RenderTarget::new(gl, width, height, spec.format) {
+                Ok(previous) => Some(previous),
+                Err(e) => {
+                    current.destroy(gl);
+                    return Err(e);
+                }
+            }
+        } else {
+            None
+        };
+        Ok(Self { current, previous })
+    }
+
+    fn matches(&self, width: u32, height: u32, spec: &TargetSpec) -> bool {
+        (self.current.width, self.current.height) == (width, height)
+            && self.current.format == spec.format
+            && self.previous.is_some() == spec.feedback
+    }
+
+    /// Texture this frame renders into
+    pub fn current(&self) -> &RenderTarget {
+        &self.current
+    }
+
+    /// Last frame's output becomes the previous frame
+    fn swap(&mut self) {
+        if let Some(previous) = &mut self.previous {
+            std::mem::swap(&mut self.current, previous);
+        }
+    }
+
+    fn clear(&self, gl: &glow::Context) {
+        for target in std::iter::once(&self.current).chain(&self.previous) {
+            target.clear(gl);
+        }
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        self.current.destroy(gl);
+        if let Some(previous) = &self.previous {
+            previous.destroy(gl);
+        }
+    }
+}
+
+/// A render target some pass needs this frame
+pub struct TargetSpec {
+    pub name: String,
+    /// The pass reads its previous frame
+    pub feedback: bool,
+    pub format: TargetFormat,
+}
+
+/// Render targets for the passes, keyed by pass name
+#[derive(Default)]
+pub struct PassTargets {
+    targets: HashMap<String, PassTarget>,
+}
+
+impl PassTargets {
+    /// Create, resize or reformat the target of every listed pass.
+    /// Targets of passes that are no longer listed are released.
+    pub fn ensure(&mut self, gl: &glow::Context, specs: &[TargetSpec], width: u32, height: u32) {
+        self.targets.retain(|name, target| {
+            let listed = specs.iter().any(|spec| &spec.name == name);
+            if !listed {
+                target.destroy(gl);
+            }
+            listed
+        });
+
+        for spec in specs {
+            if let Some(target) = self.targets.get(&spec.name) {
+                if target.matches(width, height, spec) {
+                    continue;
+                }
+                target.destroy(gl);
+                self.targets.remove(&spec.name);
+            }
+
+            match PassTarget::new(gl, width, height, spec) {
+                Ok(target) => {
+                    log::info!("Created {} target: {}x{} {}", spec.name, width, height, spec.format.label());
+                    self.targets.insert(spec.name.clone(), target);
+                }
+                Err(e) => log::error!("Failed to create {} target: {}", spec.name, e),
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PassTarget> {
+        self.targets.get(name)
+    }
+
+    /// Start a new frame: feedback passes flip their ping-pong targets
+    pub fn swap(&mut self) {
+        for target in self.targets.values_mut() {
+            target.swap();
+        }
+    }
+
+    /// Reset feedback to black, for one pass or all of them
+    pub fn clear(&self, gl: &glow::Context, name: Option<&str>) {
+        for (target_name, target) in &self.targets {
+            if name.is_none_or(|name| name == target_name) {
+                target.clear(gl);
+            }
+        }
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        for target in self.targets.values() {
+            target.destroy(gl);
+        }
+        self.targets.clear();
+    }
+}
+
+// ==========================================
+// SHADER PASSES
+// ==========================================
+
+/// A fragment shader loaded from a file, with its own uniforms and hot reload
+pub struct ShaderPass {
+    pub name: String,
+    pub enabled: bool,
+    pub format: TargetFormat,
+    pub path: Option<PathBuf>,
+    pub renderer: Option<Arc<Mutex<ShaderRenderer>>>,
+    pub uniforms: HashMap<String, UniformInfo>,
+    pub error: Arc<Mutex<Option<String>>>,
+    watcher: Option<RecommendedWatcher>,
+    update_receiver: Option<mpsc::Receiver<()>>,
+    last_reload: Instant,
+}
+
+impl ShaderPass {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            enabled: true,
+            format: TargetFormat::default(),
+            path: None,
+            renderer: None,
+            uniforms: HashMap::new(),
+            error: Arc::new(Mutex::new(None)),
+            watcher: None,
+            update_receiver: None,
+            last_reload: Instant::now(),
+        }
+    }
+
+    /// Compiled and switched on
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.renderer.is_some()
+    }
+
+    pub fn load(&mut self, gl: &glow::Context, path: PathBuf, shadertoy_mode: bool) {
+        if let (Some(watcher), Some(old_path)) = (&mut self.watcher, &self.path) {
+            let _ = watcher.unwatch(old_path);
+        }
+
+        let (tx, rx) = mpsc::channel();
+        self.watcher = ShaderApp::create_watcher(&path, tx);
+        self.update_receiver = Some(rx);
+        self.path = Some(path);
+        self.uniforms.clear();
+
+        self.reload(gl, shadertoy_mode);
+    }
+
+    /// Reload after a file change, debounced
+    pub fn poll_reload(&mut self, gl: &glow::Context, shadertoy_mode: bool) {
+        let changed = self.update_receiver.as_ref()
+            .is_some_and(|receiver| receiver.try_recv().is_ok());
+        if changed && self.last_reload.elapsed() >= Duration::from_millis(RELOAD_DEBOUNCE_MS) {
+            log::info!("{} shader file changed, attempting to reload...", self.name);
+            self.reload(gl, shadertoy_mode);
+        }
+    }
+
+    /// Recompile from disk, keeping edited uniform values
+    pub fn reload(&mut self, gl: &glow::Context, shadertoy_mode: bool) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                let source = prepare_shader_source(source, shadertoy_mode);
+                let new_uniforms = parse_uniforms(&source);
+
+                match ShaderRenderer::new(gl, &source) {
+                    Ok(new_renderer) => {
+                        let new_uniforms = reconcile_uniforms(&self.name, new_uniforms, &new_renderer);
+                        if let Some(old_renderer) = &self.renderer {
+                            old_renderer.lock().destroy(gl);
+                        }
+
+                        self.renderer = Some(Arc::new(Mutex::new(new_renderer)));
+                        *self.error.lock() = None;
+                        self.uniforms = merge_uniform_state(&self.uniforms, new_uniforms);
+                        self.last_reload = Instant::now();
+                        log::info!("{} shader loaded: {:?}", self.name, path);
+                    }
+                    Err(e) => {
+                        *self.error.lock() = Some(e.clone());
+                        log::error!("{} shader compilation failed: {}", self.name, e);
+                    }
+                }
+            }
+            Err(e) => {
+                *self.error.lock() = Some(format!("Failed to read shader file: {}", e));
+            }
+        }
+    }
+
+    /// Drop the shader and stop watching its file
+    pub fn unload(&mut self, gl: &glow::Context) {
+        if let Some(renderer) = self.renderer.take() {
+            renderer.lock().destroy(gl);
+        }
+        self.watcher = None;
+        self.update_receiver = None;
+        self.path = None;
+        self.uniforms.clear();
+        *self.error.lock() = None;
+    }
+
+    /// Other passes whose output this pass samples
+    fn inputs(&self) -> impl Iterator<Item = &str> {
+        self.uniforms.values()
+            .filter_map(|info| info.input.as_deref())
+            .filter(|input| *input != self.name)
+    }
+
+    /// Has a sampler reading the output of `pass_name`
+    pub fn samples(&self, pass_name: &str) -> bool {
+        self.inputs().any(|input| input == pass_name)
+    }
+
+    /// Reads its own previous frame
+    pub fn uses_feedback(&self) -> bool {
+        uses_feedback(&self.name, &self.uniforms)
+    }
+}
+
+// ==========================================
+// RENDER GRAPH
+// ==========================================
+
+/// One pass ready to draw, detached from the app so it can run inside a paint callback
+#[derive(Clone)]
+struct PassStep {
+    /// Label for GPU timings
+    name: String,
+    renderer: Arc<Mutex<ShaderRenderer>>,
+    uniforms: HashMap<String, UniformInfo>,
+    fbo: glow::Framebuffer,
+    size: egui::Vec2,
+}
+
+/// Active buffer passes in dependency order, with every sampler input bound to its texture
+#[derive(Clone, Default)]
+pub struct RenderGraph {
+    steps: Vec<PassStep>,
+    /// This frame's output of every pass with a target
+    outputs: HashMap<String, TextureHandle>,
+    /// Last frame's output of feedback passes
+    previous: HashMap<String, TextureHandle>,
+    /// The main shader renders here first when it reads its previous frame
+    main_target: Option<(glow::Framebuffer, u32, u32)>,
+}
+
+impl RenderGraph {
+    /// Order the active passes so every pass renders after the passes it samples.
+    /// Passes that sample each other in a cycle are skipped and reported in the error.
+    pub fn build(passes: &[ShaderPass], targets: &PassTargets) -> (Self, Option<String>) {
+        let active: Vec<&ShaderPass> = passes.iter()
+            .filter(|pass| pass.is_active() && targets.get(&pass.name).is_some())
+            .collect();
+
+        let mut graph = Self::default();
+        for (name, target) in &targets.targets {
+            graph.outputs.insert(name.clone(), target.current.texture_handle(name));
+            if let Some(previous) = &target.previous {
+                graph.previous.insert(name.clone(), previous.texture_handle(name));
+            }
+        }
+        graph.main_target = targets.get(MAIN_PASS_NAME)
+            .map(|target| (target.current.fbo, target.current.width, target.current.height));
+
+        let (order, cyclic) = resolve_order(&active);
+        for index in order {
+            let pass = active[index];
+            let (Some(renderer), Some(target)) = (&pass.renderer, targets.get(&pass.name)) else {
+                continue;
+            };
+            let mut uniforms = pass.uniforms.clone();
+            graph.bind_inputs(&mut uniforms, &pass.name);
+            graph.steps.push(PassStep {
+                name: pass.name.clone(),
+                renderer: renderer.clone(),
+                uniforms,
+                fbo: target.current.fbo,
+                size: egui::Vec2::new(target.current.width as f32, target.current.height as f32),
+            });
+        }
+
+        let error = (!cyclic.is_empty()).then(|| {
+            let names: Vec<&str> = cyclic.iter().map(|&index| active[index].name.as_str()).collect();
+            format!("Buffer passes sample each other in a cycle and are skipped: {}", names.join(", "))
+        });
+        (graph, error)
+    }
+
+    /// Point sampler uniforms with an input at the output of that pass, read with their own `input_params`.
+    /// A pass sampling itself, or `u_prevFrame`, gets its previous frame, and so does a buffer
+    /// sampling the Image pass, which only renders after the buffers.
+    pub fn bind_inputs(&self, uniforms: &mut HashMap<String, UniformInfo>, pass_name: &str) {
+        for (name, info) in uniforms.iter_mut() {
+            if info.uniform_type != UniformType::Sampler2D {
+                continue;
+            }
+            let texture = match info.input.as_deref() {
+                Some(input) if input == pass_name => self.previous.get(pass_name),
+                Some(MAIN_PASS_NAME) if BUFFER_NAMES.contains(&pass_name) => self.previous.get(MAIN_PASS_NAME),
+                Some(input) => self.outputs.get(input),
+                None if name == PREV_FRAME_UNIFORM => self.previous.get(pass_name),
+                None => continue,
+            };
+            let params = info.input_params;
+            info.value = UniformValue::Sampler2D(texture.map(|handle| TextureHandle { params, ..handle.clone() }));
+        }
+    }
+
+    /// Draw every pass into its target. Leaves the framebuffer binding changed.
+    pub fn render(&self, gl: &glow::Context, inputs: &FrameInputs, timer: &PassTimer) {
+        for step in &self.steps {
+            let inputs = FrameInputs { resolution: step.size, ..*inputs };
+            unsafe {
+                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(step.fbo));
+                gl.viewport(0, 0, step.size.x as i32, step.size.y as i32);
+                gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+            }
+            timer.time(gl, &step.name, || step.renderer.lock().paint(gl, &inputs, &step.uniforms));
+        }
+    }
+}
+
+// ==========================================
+// POST-PROCESS CHAIN
+// ==========================================
+
+/// Enabled post-process stages in order, each reading the previous stage through `u_mainPass`
+#[derive(Clone, Default)]
+pub struct PostChain {
+    steps: Vec<PassStep>,
+    /// Framebuffer the main shader renders into before the first stage
+    source: Option<glow::Framebuffer>,
+}
+
+impl PostChain {
+    /// Every stage but the last writes into its own target, in the stage's format
+    pub fn build(stages: &[ShaderPass], graph: &RenderGraph, targets: &PassTargets) -> Self {
+        let Some(source) = targets.get(POST_SOURCE_NAME) else {
+            return Self::default();
+        };
+
+        let active: Vec<&ShaderPass> = stages.iter().filter(|stage| stage.is_active()).collect();
+        let mut steps = Vec::with_capacity(active.len());
+        let mut read = &source.current;
+        for (index, stage) in active.iter().enumerate() {
+            let Some(renderer) = &stage.renderer else {
+                continue;
+            };
+            let write = if index + 1 == active.len() {
+                None
+            } else {
+                match targets.get(&stage.name) {
+                    Some(target) => Some(&target.current),
+                    None => continue,
+                }
+            };
+
+            let mut uniforms = stage.uniforms.clone();
+            graph.bind_inputs(&mut uniforms, &stage.name);
+            uniforms.insert(
+                MAIN_PASS_UNIFORM.to_string(),
+                UniformInfo {
+                    value: UniformValue::Sampler2D(Some(read.texture_handle("main_pass"))),
+                    ..UniformInfo::new(UniformType::Sampler2D)
+                },
+            );
+            // The last stage's framebuffer is replaced by the destination when rendering
+            let target = write.unwrap_or(read);
+            steps.push(PassStep {
+                name: stage.name.clone(),
+                renderer: renderer.clone(),
+                uniforms,
+                fbo: target.fbo,
+                size: egui::Vec2::new(target.width as f32, target.height as f32),
+            });
+            if let Some(write) = write {
+                read = write;
+            }
+        }
+
+        Self {
+            source: (!steps.is_empty()).then_some(source.current.fbo),
+            steps,
+        }
+    }
+
+    /// Framebuffer the main shader should draw into, `None` when there are no stages
+    pub fn source(&self) -> Option<glow::Framebuffer> {
+        self.source
+    }
+
+    /// Run every stage; the last one draws into `destination` at `viewport`
+    pub fn render(&self, gl: &glow::Context, inputs: &FrameInputs, destination: Option<glow::Framebuffer>, viewport: [i32; 4], timer: &PassTimer) {
+        for (index, step) in self.steps.iter().enumerate() {
+            let inputs = FrameInputs { resolution: step.size, ..*inputs };
+            unsafe {
+                if index + 1 == self.steps.len() {
+                    let [x, y, w, h] = viewport;
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, destination);
+                    gl.viewport(x, y, w, h);
+                } else {
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(step.fbo));
+                    gl.viewport(0, 0, step.size.x as i32, step.size.y as i32);
+                    gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                    gl.clear(glow::COLOR_BUFFER_BIT);
+                }
+            }
+            timer.time(gl, &step.name, || step.renderer.lock().paint(gl, &inputs, &step.uniforms));
+        }
+    }
+}
+
+// ==========================================
+// FRAME OUTPUT
+// ==========================================
+
+/// Built-in final pass: exposure, tonemapping, then clamping for the 8-bit output
+pub const PRESENT_SHADER: &str = r#"#version 330 core
+precision highp float;
+
+in vec2 v_uv;
+uniform sampler2D u_source;
+uniform int u_tonemap;
+uniform float u_exposure;
+
+out vec4 out_color;
+
+vec3 aces(vec3 x) {
+    return (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+}
+
+vec3 hable(vec3 x) {
+    const float A = 0.15, B = 0.50, C = 0.10, D = 0.20, E = 0.02, F = 0.30;
+    return (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F;
+}
+
+void main() {
+    vec4 color = texture(u_source, v_uv);
+    vec3 rgb = max(color.rgb, 0.0) * exp2(u_exposure);
+    if (u_tonemap == 1) {
+        rgb = rgb / (1.0 + rgb);
+    } else if (u_tonemap == 2) {
+        rgb = aces(rgb);
+    } else if (u_tonemap == 3) {
+        rgb = hable(rgb * 2.0) / hable(vec3(11.2));
+    }
+    out_color = clamp(vec4(rgb, color.a), 0.0, 1.0);
+}
+"#;
+
+/// Built-in pass that filters a supersampled image down by an integer factor
+pub const DOWNSAMPLE_SHADER: &str = r#"#version 330 core
+precision highp float;
+
+uniform sampler2D u_source;
+uniform int u_factor;
+uniform int u_filter;
+
+out vec4 out_color;
+
+const float PI = 3.14159265;
+
+// Kernels take the distance in output pixels
+float sinc(float x) {
+    return x == 0.0 ? 1.0 : sin(PI * x) / (PI * x);
+}
+
+float kernel(float x) {
+    x = abs(x);
+    if (u_filter == 0) {
+        return x < 0.5 ? 1.0 : 0.0;
+    } else if (u_filter == 1) {
+        return max(1.0 - x, 0.0);
+    }
+    return x < 2.0 ? sinc(x) * sinc(x / 2.0) : 0.0;
+}
+
+void main() {
+    float factor = float(u_factor);
+    float radius = u_filter == 0 ? 0.5 : (u_filter == 1 ? 1.0 : 2.0);
+    ivec2 size = textureSize(u_source, 0);
+    vec2 center = gl_FragCoord.xy * factor;
+
+    ivec2 first = ivec2(floor(center - radius * factor));
+    ivec2 last = ivec2(ceil(center + radius * factor));
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int y = first.y; y < last.y; y++) {
+        for (int x = first.x; x < last.x; x++) {
+            vec2 offset = (vec2(x, y) + 0.5 - center) / factor;
+            float weight = kernel(offset.x) * kernel(offset.y);
+            if (weight == 0.0) {
+                continue;
+            }
+            ivec2 texel = clamp(ivec2(x, y), ivec2(0), size - 1);
+            // Clamp first so each sample looks as it would on screen
+            sum += clamp(texelFetch(u_source, texel, 0), 0.0, 1.0) * weight;
+            total += weight;
+        }
+    }
+    out_color = clamp(sum / total, 0.0, 1.0);
+}
+"#;
+
+/// Filter `source` down into `destination`, which must be `factor` times smaller
+pub fn render_downsample(
+    gl: &glow::Context,
+    renderer: &ShaderRenderer,
+    source: &RenderTarget,
+    factor: u32,
+    filter: DownsampleFilter,
+    destination: &RenderTarget,
+) {
+    let mut uniforms = HashMap::new();
+    let mut insert = |name: &str, uniform_type, value| {
+        uniforms.insert(name.to_string(), UniformInfo { value, ..UniformInfo::new(uniform_type) });
+    };
+    insert("u_source", UniformType::Sampler2D, UniformValue::Sampler2D(Some(source.texture_handle("supersampled"))));
+    insert("u_factor", UniformType::Int, UniformValue::Int(factor as i32));
+    insert("u_filter", UniformType::Int, UniformValue::Int(filter as i32));
+
+    unsafe {
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(destination.fbo));
+        gl.viewport(0, 0, destination.width as i32, destination.height as i32);
+    }
+    let inputs = FrameInputs {
+        resolution: egui::Vec2::new(destination.width as f32, destination.height as f32),
+        ..FrameInputs::default()
+    };
+    renderer.paint(gl, &inputs, &uniforms);
+}
+
+/// Final pass from the output target to the screen or export framebuffer
+#[derive(Clone)]
+pub struct Present {
+    step: PassStep,
+}
+
+impl Present {
+    /// `None` when the output needs no processing or its target is missing
+    pub fn build(renderer: &Arc<Mutex<ShaderRenderer>>, settings: &OutputSettings, targets: &PassTargets) -> Option<Self> {
+        if !settings.needs_present() {
+            return None;
+        }
+        let target = &targets.get(OUTPUT_TARGET_NAME)?.current;
+
+        let mut uniforms = HashMap::new();
+        let mut insert = |name: &str, uniform_type, value| {
+            uniforms.insert(name.to_string(), UniformInfo { value, ..UniformInfo::new(uniform_type) });
+        };
+        insert("u_source", UniformType::Sampler2D, UniformValue::Sampler2D(Some(target.texture_handle("output"))));
+        insert("u_tonemap", UniformType::Int, UniformValue::Int(settings.tonemap as i32));
+        insert("u_exposure", UniformType::Float, UniformValue::Float(settings.exposure));
+
+        Some(Self {
+            step: PassStep {
+                name: OUTPUT_TARGET_NAME.to_string(),
+                renderer: renderer.clone(),
+                uniforms,
+                fbo: target.fbo,
+                size: egui::Vec2::new(target.width as f32, target.height as f32),
+            },
+        })
+    }
+
+    /// Framebuffer the rest of the frame renders into
+    pub fn source(&self) -> glow::Framebuffer {
+        self.step.fbo
+    }
+
+    pub fn render(&self, gl: &glow::Context, inputs: &FrameInputs, destination: Option<glow::Framebuffer>, viewport: [i32; 4], timer: &PassTimer) {
+        let [x, y, w, h] = viewport;
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, destination);
+            gl.viewport(x, y, w, h);
+        }
+        // Reads its source pixel for pixel, also when the frame is drawn in tiles
+        let inputs = FrameInputs { resolution: self.step.size, tile: [0.0; 4], ..*inputs };
+        timer.time(gl, &self.step.name, || self.step.renderer.lock().paint(gl, &inputs, &self.step.uniforms));
+    }
+}
+
+/// Everything needed to draw one frame, detached from the app for paint callbacks and exports
+#[derive(Clone)]
+pub struct FramePlan {
+    pub graph: RenderGraph,
+    pub post_chain: PostChain,
+    pub present: Option<Present>,
+    pub main_renderer: Arc<Mutex<ShaderRenderer>>,
+    /// Main shader uniforms with sampler inputs already bound
+    pub main_uniforms: HashMap<String, UniformInfo>,
+    /// Receives GPU timings of every pass when profiling
+    pub timer: PassTimer,
+}
+
+impl FramePlan {
+    /// Draw buffers, main shader, post-processing and output into `destination` at `viewport`.
+    /// Offscreen passes render at the size of the tile, or `inputs.resolution` when not tiling.
+    pub fn render(&self, gl: &glow::Context, inputs: &FrameInputs, destination: Option<glow::Framebuffer>, viewport: [i32; 4]) {
+        let size = inputs.target_size();
+        let full = [0, 0, size.x as i32, size.y as i32];
+
+        self.timer.begin_frame(gl);
+
+        // Buffer passes leave their own framebuffer bound
+        self.graph.render(gl, inputs, &self.timer);
+
+        // With an output pass the frame stays in its own format until it is tonemapped
+        let (target, target_viewport) = match &self.present {
+            Some(present) => (Some(present.source()), full),
+            None => (destination, viewport),
+        };
+
+        unsafe {
+            match self.post_chain.source() {
+                Some(source) => {
+                    // === Main shader into the post-process source ===
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(source));
+                    gl.viewport(full[0], full[1], full[2], full[3]);
+                    gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                    gl.clear(glow::COLOR_BUFFER_BIT);
+                    self.render_main(gl, inputs, Some(source), full);
+
+                    // === Post-process chain, the last stage draws to the target ===
+                    self.post_chain.render(gl, inputs, target, target_viewport, &self.timer);
+                }
+                None => {
+                    let [x, y, w, h] = target_viewport;
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, target);
+                    gl.viewport(x, y, w, h);
+                    if self.present.is_some() {
+                        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                        gl.clear(glow::COLOR_BUFFER_BIT);
+                    }
+                    self.render_main(gl, inputs, target, target_viewport);
+                }
+            }
+        }
+
+        if let Some(present) = &self.present {
+            present.render(gl, inputs, destination, viewport, &self.timer);
+        }
+        self.timer.end_frame();
+    }
+
+    /// Framebuffer holding each stage of the frame once `render` returns, in drawing order.
+    /// `None` is the destination passed to `render`.
+    pub fn stage_framebuffers(&self) -> Vec<(String, Option<glow::Framebuffer>)> {
+        let output = self.present.as_ref().map(Present::source);
+        let mut stages = vec![(MAIN_PASS_NAME.to_string(), self.post_chain.source().or(output))];
+        let post = &self.post_chain.steps;
+        for (index, step) in post.iter().enumerate() {
+            let fbo = if index + 1 == post.len() { output } else { Some(step.fbo) };
+            stages.push((step.name.clone(), fbo));
+        }
+        if self.present.is_some() {
+            stages.push((OUTPUT_TARGET_NAME.to_string(), None));
+        }
+        stages
+    }
+
+    /// Draw the main shader into `destination` (already bound, with `viewport` set).
+    /// With feedback it renders into its own target first and is copied over.
+    fn render_main(&self, gl: &glow::Context, inputs: &FrameInputs, destination: Option<glow::Framebuffer>, viewport: [i32; 4]) {
+        let paint = || self.timer.time(gl, MAIN_PASS_NAME, || self.main_renderer.lock().paint(gl, inputs, &self.main_uniforms));
+        let Some((fbo, width, height)) = self.graph.main_target else {
+            paint();
+            return;
+        };
+
+        let [x, y, w, h] = viewport;
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+            gl.viewport(0, 0, width as i32, height as i32);
+            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            paint();
+
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(fbo));
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, destination);
+            gl.blit_framebuffer(
+                0, 0, width as i32, height as i32,
+                x, y, x + w, y + h,
+                glow::COLOR_BUFFER_BIT, glow::LINEAR,
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, destination);
+            gl.viewport(x, y, w, h);
+        }
+    }
+}
+
+/// Topological order of the passes, and the passes that sample each other in a cycle.
+/// Cycle members are left out of the order; passes that only sample them still render.
+/// Sampling a pass that isn't active adds no edge.
+fn resolve_order(passes: &[&ShaderPass]) -> (Vec<usize>, Vec<usize>) {
+    let index_of: HashMap<&str, usize> = passes.iter()
+        .enumerate()
+        .map(|(index, pass)| (pass.name.as_str(), index))
+        .collect();
+
+    let dependencies: Vec<Vec<usize>> = passes.iter()
+        .map(|pass| {
+            let mut deps: Vec<usize> = pass.inputs()
+                .filter_map(|input| index_of.get(input).copied())
+                .collect();
+            deps.sort_unstable();
+            deps.dedup();
+            deps
+        })
+        .collect();
+
+    let mut order = Vec::with_capacity(passes.len());
+    let mut cyclic = Vec::new();
+    let mut done = vec![false; passes.len()];
+    while order.len() + cyclic.len() < passes.len() {
+        // Lowest-numbered ready pass first, so independent passes keep A-D order
+        let ready = (0..passes.len())
+            .find(|&index| !done[index] && dependencies[index].iter().all(|&dep| done[dep]));
+
+        match ready {
+            Some(index) => {
+                done[index] = true;
+                order.push(index);
+            }
+            None => {
+                // Only passes that reach themselves are in a cycle; the rest wait on them
+                let stuck: Vec<usize> = (0..passes.len())
+                    .filter(|&index| !done[index] && reaches(&dependencies, &done, index, index))
+                    .collect();
+                for &index in &stuck {
+                    done[index] = true;
+                }
+                cyclic.extend(stuck);
+            }
+        }
+    }
+
+    (order, cyclic)
+}
+
+/// `to` is reachable from `from` through the dependencies of passes not yet done
+fn reaches(dependencies: &[Vec<usize>], done: &[bool], from: usize, to: usize) -> bool {
+    let mut visited = vec![false; dependencies.len()];
+    let mut stack: Vec<usize> = dependencies[from].clone();
+    while let Some(index) = stack.pop() {
+        if index == to {
+            return true;
+        }
+        if done[index] || visited[index] {
+            continue;
+        }
+        visited[index] = true;
+        stack.extend(&dependencies[index]);
+    }
+    false
+}
@@ -1,13 +1,19 @@
-use crate::app::ShaderApp;
+use crate::app::{current_date, ShaderApp};
 use super::data::*;
 use super::file_io;
+use super::inspector::{component_byte, PixelInspector};
+use super::keyboard::KEYBOARD_UNIFORM;
+use super::profiler::BenchmarkResult;
+use super::render_graph::{uses_feedback, ShaderPass, BUFFER_NAMES, MAIN_PASS_NAME, MAIN_PASS_UNIFORM, PREV_FRAME_UNIFORM};
 use crate::FILE_CHECK_TIMEOUT_MS;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::path::Path;
 
 impl eframe::App for ShaderApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let frame_start = Instant::now();
+
         // Check for hot-reload messages
         if self.shader_update_receiver
             .recv_timeout(Duration::from_millis(FILE_CHECK_TIMEOUT_MS))
@@ -15,13 +21,11 @@ impl eframe::App for ShaderApp {
         {
             self.try_reload_shader();
         }
-        
-        // Check for post-process hot-reload
-        if let Some(receiver) = &self.post_process_update_receiver {
-            if receiver.recv_timeout(Duration::from_millis(FILE_CHECK_TIMEOUT_MS)).is_ok() {
-                self.try_reload_post_process();
-            }
-        }
+
+        self.poll_pass_reloads();
+        self.poll_image_export();
+        self.poll_benchmark();
+        self.update_keyboard(ctx);
 
         egui::SidePanel::right("controls_panel")
             .default_width(250.0) 
@@ -60,6 +64,20 @@ impl eframe::App for ShaderApp {
                         }
                     });
 
+                    if ui
+                        .checkbox(&mut self.shadertoy_mode, "Shadertoy mode")
+                        .on_hover_text("Wrap sources that define mainImage() and provide iTime, iResolution, iChannel0..3, ...")
+                        .changed()
+                    {
+                        self.reload_shader();
+                        self.reload_passes();
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("Format:");
+                        render_format_combo(ui, "main_format", &mut self.main_format);
+                    });
+
                     ui.separator();
 
                     // Time controls
@@ -70,68 +88,187 @@ impl eframe::App for ShaderApp {
                         }
                         if ui.button("Reset").clicked() {
                             self.time = 0.0;
+                            self.frame_count = 0;
+                            self.reset_feedback(None);
+                        }
+                        if uses_feedback(MAIN_PASS_NAME, &self.uniforms)
+                            && ui.button("Clear feedback").on_hover_text("Reset u_prevFrame to black").clicked()
+                        {
+                            self.reset_feedback(Some(MAIN_PASS_NAME));
                         }
                     });
 
                     ui.separator();
 
-                    // Post-processing section
-                    ui.label(egui::RichText::new("Post-Processing:").strong());
-                    
-                    ui.horizontal(|ui| {
-                        if ui.checkbox(&mut self.post_process_enabled, "Enable").changed() {
-                            log::info!("Post-process: {}", self.post_process_enabled);
-                        }
-                        
-                        if let Some(path) = &self.post_process_shader_path {
-                            ui.label(egui::RichText::new(
-                                path.file_name()
-                                    .and_then(|n| n.to_str())
-                                    .unwrap_or("unknown")
-                            ).small().family(egui::FontFamily::Monospace));
+                    // Post-processing chain
+                    ui.label(egui::RichText::new("Post-Processing:").strong())
+                        .on_hover_text("Stages run top to bottom, each reads the previous output as u_mainPass");
+
+                    let stage_count = self.post_stages.len();
+                    let mut stage_action = None;
+                    for (index, stage) in self.post_stages.iter_mut().enumerate() {
+                        if let Some(action) = render_post_stage(ui, stage, index, stage_count, &self.gl) {
+                            stage_action = Some((index, action));
                         }
-                    });
+                    }
+                    match stage_action {
+                        Some((index, StageAction::MoveUp)) => self.post_stages.swap(index - 1, index),
+                        Some((index, StageAction::MoveDown)) => self.post_stages.swap(index, index + 1),
+                        Some((index, StageAction::Remove)) => self.remove_post_stage(index),
+                        None => {}
+                    }
 
-                    if ui.button("Load Post-Process Shader...").clicked() {
-                        if let Some(path) = rfd::FileDialog::new()
+                    if ui.button("Add Post-Process Stage...").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
                             .add_filter("GLSL Fragment Shader", &["frag", "glsl"])
+                            .set_directory(&self.post_shader_dir)
                             .pick_file()
-                        {
-                            self.load_post_process_shader(path);
-                        }
+                    {
+                        self.add_post_stage(path);
                     }
 
-                    // Show post-process error if any
-                    if let Some(error) = self.post_process_error.lock().clone() {
-                        ui.label(
-                            egui::RichText::new("Post-process error:")
-                                .color(egui::Color32::RED)
-                                .small()
-                        );
+                    ui.separator();
+
+                    // Buffer passes
+                    ui.label(egui::RichText::new("Buffers:").strong())
+                        .on_hover_text("Offscreen passes rendered before the main shader. Sample one with `uniform sampler2D u_bufferA;` or pick it as a sampler's source.");
+                    let mut cleared = Vec::new();
+                    for pass in &mut self.buffers {
+                        if render_buffer_pass(ui, pass, &self.gl, self.shadertoy_mode) {
+                            cleared.push(pass.name.clone());
+                        }
+                    }
+                    for name in cleared {
+                        self.reset_feedback(Some(&name));
+                    }
+                    if let Some(error) = self.graph_error.lock().clone() {
                         ui.label(
-                            egui::RichText::new(&error)
+                            egui::RichText::new(error)
                                 .color(egui::Color32::LIGHT_RED)
-                                .family(egui::FontFamily::Monospace)
                                 .small()
                         );
                     }
 
                     ui.separator();
 
+                    // Preview resolution
+                    ui.label(egui::RichText::new("Preview:").strong());
+                    ui.checkbox(&mut self.fixed_resolution, "Fixed resolution")
+                        .on_hover_text("Render at the export resolution, letterboxed into the canvas, to see the exact framing of an export");
+                    ui.add_enabled(
+                        !self.fixed_resolution,
+                        egui::Slider::new(&mut self.render_scale, 0.25..=2.0)
+                            .text("Render scale")
+                            .custom_formatter(|value, _| format!("{:.0}%", value * 100.0))
+                            .custom_parser(|text| text.trim_end_matches('%').trim().parse::<f64>().ok().map(|percent| percent / 100.0)),
+                    ).on_hover_text("Render below canvas resolution to keep heavy shaders interactive, or above it for anti-aliasing");
+                    if ui.checkbox(&mut self.show_hud, "Performance overlay")
+                        .on_hover_text("FPS, CPU frame time and GPU time per pass, with a graph of frame time (green) and GPU time (red)")
+                        .changed()
+                        && !self.show_hud
+                    {
+                        self.profiler.lock().reset(&self.gl);
+                    }
+                    if ui.checkbox(&mut self.inspector.enabled, "Pixel inspector")
+                        .on_hover_text("Read back the pixel under the pointer from the main shader and every post-process stage. Right-click the canvas to pin a pixel.")
+                        .changed()
+                    {
+                        self.inspector.pinned = None;
+                    }
+                    if self.inspector.enabled {
+                        let targets = self.color_uniform_targets();
+                        if let Some((pass, name, rgba)) = render_pixel_inspector(ui, &mut self.inspector, &targets) {
+                            self.copy_color_to_uniform(&pass, &name, rgba);
+                        }
+                    }
+
+                    ui.separator();
+
+                    // Output section
+                    ui.label(egui::RichText::new("Output:").strong())
+                        .on_hover_text("Applied to the final image; passes keep their own format until then");
+                    ui.horizontal(|ui| {
+                        ui.label("Tonemap:");
+                        egui::ComboBox::from_id_source("output_tonemap")
+                            .selected_text(self.output.tonemap.label())
+                            .show_ui(ui, |ui| {
+                                for tonemap in Tonemap::ALL {
+                                    ui.selectable_value(&mut self.output.tonemap, tonemap, tonemap.label());
+                                }
+                            });
+                    });
+                    ui.add(egui::Slider::new(&mut self.output.exposure, -8.0..=8.0).text("Exposure (EV)"));
+
+                    ui.separator();
+
                     // Export section
                     ui.label(egui::RichText::new("Export:").strong());
                     ui.horizontal(|ui| {
                         ui.label("Width:");
-                        ui.add(egui::DragValue::new(&mut self.export_resolution[0]).speed(10).clamp_range(1..=8192));
+                        ui.add(egui::DragValue::new(&mut self.export_resolution[0]).speed(10).clamp_range(1..=32768));
                     });
                     ui.horizontal(|ui| {
                         ui.label("Height:");
-                        ui.add(egui::DragValue::new(&mut self.export_resolution[1]).speed(10).clamp_range(1..=8192));
+                        ui.add(egui::DragValue::new(&mut self.export_resolution[1]).speed(10).clamp_range(1..=32768));
                     });
+                    render_date_editor(ui, &mut self.export_date);
+                    if self.export_needs_tiles(self.export_resolution[0], self.export_resolution[1]) {
+                        ui.label(egui::RichText::new(format!("Rendered in tiles of up to {}px", file_io::EXPORT_TILE_SIZE)).small())
+                            .on_hover_text("Only the main shader can be exported in tiles; buffers, feedback and post-processing must be off");
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Supersampling:");
+                        egui::ComboBox::from_id_source("export_supersampling")
+                            .selected_text(supersampling_label(self.export_supersampling))
+                            .show_ui(ui, |ui| {
+                                for factor in 1..=4 {
+                                    ui.selectable_value(&mut self.export_supersampling, factor, supersampling_label(factor));
+                                }
+                            });
+                    }).response.on_hover_text("Render image and video exports at a multiple of the resolution and filter down, to remove aliasing");
+                    if self.export_supersampling > 1 {
+                        ui.horizontal(|ui| {
+                            ui.label("Filter:");
+                            egui::ComboBox::from_id_source("export_filter")
+                                .selected_text(self.export_filter.label())
+                                .show_ui(ui, |ui| {
+                                    for filter in DownsampleFilter::ALL {
+                                        ui.selectable_value(&mut self.export_filter, filter, filter.label());
+                                    }
+                                });
+                        });
+                    }
                     ui.add_space(4.0);
-                    if ui.button("Export Image").clicked() {
+                    if self.image_replay.is_some() {
+                        if let Some(progress) = self.export_progress.lock().clone() {
+                            let progress_fraction = progress.current_frame as f32 / progress.total_frames as f32;
+                            ui.add(egui::ProgressBar::new(progress_fraction)
+                                .text(&progress.status));
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.cancel_image_export();
+                        }
+                    } else if ui.button("Export Image").clicked() {
                         self.export_image();
                     }
+                    if let Some(run) = &self.benchmark_run {
+                        let (done, total) = run.progress();
+                        ui.add(egui::ProgressBar::new(done as f32 / total as f32)
+                            .text(format!("Benchmark frame {}/{}", done, total)));
+                        if ui.button("Cancel").clicked() {
+                            self.cancel_benchmark();
+                        }
+                    } else {
+                        ui.horizontal(|ui| {
+                            if ui.button("Benchmark").clicked() {
+                                self.start_benchmark();
+                            }
+                            ui.add(egui::DragValue::new(&mut self.benchmark_frames).speed(10).clamp_range(1..=10000).suffix(" frames"));
+                        }).response.on_hover_text("Render frames at the export resolution, waiting for the GPU after each, and time them");
+                    }
+                    if let Some(result) = &self.benchmark_result {
+                        render_benchmark_result(ui, result);
+                    }
 
                     ui.add_space(8.0);
 
@@ -153,14 +290,16 @@ impl eframe::App for ShaderApp {
                         format!("Duration: {:.2}s", self.video_duration_frames as f32 / self.video_fps as f32)
                     ).small());
                     
-                    if let Some(progress) = self.export_progress.lock().clone() {
+                    if let Some(progress) = self.export_progress.lock().clone()
+                        && self.image_replay.is_none()
+                    {
                         ui.add_space(4.0);
                         let progress_fraction = progress.current_frame as f32 / progress.total_frames as f32;
                         ui.add(egui::ProgressBar::new(progress_fraction)
                             .text(&progress.status));
                     } else {
                         ui.add_space(4.0);
-                        if ui.button("Export Video").clicked() {
+                        if ui.add_enabled(self.image_replay.is_none(), egui::Button::new("Export Video")).clicked() {
                             self.export_video();
                         }
                     }
@@ -170,17 +309,10 @@ impl eframe::App for ShaderApp {
                     // Main shader uniforms
                     if !self.uniforms.is_empty() {
                         ui.label(egui::RichText::new("Main Shader Uniforms:").strong());
-                        render_uniform_controls(ui, &mut self.uniforms, &self.gl);
+                        render_uniform_controls(ui, "main_uniforms", &mut self.uniforms, &self.gl);
                         ui.separator();
                         }
 
-                    // Post-process shader uniforms
-                    if self.post_process_enabled && !self.post_process_uniforms.is_empty() {
-                        ui.label(egui::RichText::new("Post-Process Uniforms:").strong());
-                        render_uniform_controls(ui, &mut self.post_process_uniforms, &self.gl);
-                        ui.separator();
-                    }
-
                     // Display main shader compilation errors
                     let error_text = self.shader_error.lock().clone();
                     if let Some(error) = error_text {
@@ -209,108 +341,842 @@ impl eframe::App for ShaderApp {
             });
         });
 
+        if self.show_hud {
+            let interval = ctx.input(|i| i.unstable_dt);
+            self.profiler.lock().record_cpu(frame_start.elapsed(), interval);
+        }
+
         ctx.request_repaint();
     }
     
     fn on_exit(&mut self, gl: Option<&glow::Context>) {
         if let Some(gl) = gl {
             self.shader_renderer.lock().destroy(gl);
+            self.present_renderer.lock().destroy(gl);
+            self.downsample_renderer.lock().destroy(gl);
+            self.profiler.lock().destroy(gl);
             
-            if let Some(post_renderer) = &self.post_process_renderer {
-                post_renderer.lock().destroy(gl);
+            for pass in self.buffers.iter_mut().chain(&mut self.post_stages) {
+                pass.unload(gl);
             }
+            self.pass_targets.destroy(gl);
             
-            // Clean up intermediate framebuffer
-            unsafe {
-                use glow::HasContext as _;
-                if let Some(fbo) = self.intermediate_fbo {
-                    gl.delete_framebuffer(fbo);
+            self.keyboard.destroy(gl);
+        }
+    }
+}
+
+/// Reordering and removal requested from a post-process stage header
+enum StageAction {
+    MoveUp,
+    MoveDown,
+    Remove,
+}
+
+/// Controls, errors and uniforms of one post-process stage
+fn render_post_stage(
+    ui: &mut egui::Ui,
+    stage: &mut ShaderPass,
+    index: usize,
+    stage_count: usize,
+    gl: &glow::Context,
+) -> Option<StageAction> {
+    let mut action = None;
+
+    ui.push_id(&stage.name, |ui| {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut stage.enabled, "");
+            ui.label(egui::RichText::new(format!("{}.", index + 1)).small().weak());
+            ui.label(egui::RichText::new(
+                stage.path.as_ref()
+                    .and_then(|path| path.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+            ).small().family(egui::FontFamily::Monospace));
+
+            if ui.add_enabled(index > 0, egui::Button::new("⏶").small()).on_hover_text("Move up").clicked() {
+                action = Some(StageAction::MoveUp);
+            }
+            if ui.add_enabled(index + 1 < stage_count, egui::Button::new("⏷").small()).on_hover_text("Move down").clicked() {
+                action = Some(StageAction::MoveDown);
+            }
+            if ui.small_button("✖").on_hover_text("Remove stage").clicked() {
+                action = Some(StageAction::Remove);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new("Format:").small());
+            render_format_combo(ui, "stage_format", &mut stage.format);
+        });
+
+        // Show post-process error if any
+        if let Some(error) = stage.error.lock().clone() {
+            ui.label(
+                egui::RichText::new("Post-process error:")
+                    .color(egui::Color32::RED)
+                    .small()
+            );
+            ui.label(
+                egui::RichText::new(&error)
+                    .color(egui::Color32::LIGHT_RED)
+                    .family(egui::FontFamily::Monospace)
+                    .small()
+            );
+        }
+        if uses_feedback(&stage.name, &stage.uniforms) {
+            ui.label(
+                egui::RichText::new("Post-process stages have no previous frame, u_prevFrame and samplers of this stage stay unbound")
+                    .color(egui::Color32::YELLOW)
+                    .small()
+            );
+        }
+
+        if stage.enabled && !stage.uniforms.is_empty() {
+            egui::CollapsingHeader::new("Uniforms")
+                .id_source("stage_uniforms")
+                .default_open(true)
+                .show(ui, |ui| {
+                    render_uniform_controls(ui, "post_uniforms", &mut stage.uniforms, gl);
+                });
+        }
+    });
+
+    action
+}
+
+/// Header, file controls, errors and uniforms of one buffer pass, returns true when its feedback should be cleared
+fn render_buffer_pass(ui: &mut egui::Ui, pass: &mut ShaderPass, gl: &glow::Context, shadertoy_mode: bool) -> bool {
+    let mut clear_feedback = false;
+
+    let file_name = pass.path.as_ref()
+        .and_then(|path| path.file_name())
+        .and_then(|n| n.to_str())
+        .map(str::to_string);
+
+    let title = match &file_name {
+        Some(file_name) => format!("{} · {}", pass.name, file_name),
+        None => pass.name.clone(),
+    };
+
+    egui::CollapsingHeader::new(title)
+        .id_source(("buffer_pass", &pass.name))
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut pass.enabled, "Enable");
+
+                if ui.button("Open").clicked()
+                    && let Some(path) = rfd::FileDialog::new()
+                        .add_filter("GLSL Fragment Shader", &["frag", "glsl"])
+                        .pick_file()
+                {
+                    pass.load(gl, path, shadertoy_mode);
                 }
-                if let Some(tex) = self.intermediate_texture {
-                    gl.delete_texture(tex);
+
+                if pass.path.is_some() && ui.button("Remove").clicked() {
+                    pass.unload(gl);
                 }
+
+                if pass.uses_feedback() {
+                    clear_feedback = ui.button("Clear").on_hover_text("Reset the previous frame to black").clicked();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Format:");
+                render_format_combo(ui, ("buffer_format", &pass.name), &mut pass.format);
+            });
+
+            if let Some(error) = pass.error.lock().clone() {
+                ui.label(
+                    egui::RichText::new(&error)
+                        .color(egui::Color32::LIGHT_RED)
+                        .family(egui::FontFamily::Monospace)
+                        .small()
+                );
+            }
+
+            if !pass.uniforms.is_empty() {
+                render_uniform_controls(ui, &format!("buffer_uniforms_{}", pass.name), &mut pass.uniforms, gl);
+            }
+        });
+
+    clear_feedback
+}
+
+/// Export date as year, month, day and time of day, stored like `u_date`
+fn render_date_editor(ui: &mut egui::Ui, date: &mut [f32; 4]) {
+    let mut parts = [date[0] as i32, date[1] as i32 + 1, date[2] as i32];
+    let mut seconds = date[3];
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Date:");
+        changed |= ui.add(egui::DragValue::new(&mut parts[0]).speed(0.2).clamp_range(1..=9999)).changed();
+        changed |= ui.add(egui::DragValue::new(&mut parts[1]).speed(0.1).clamp_range(1..=12)).changed();
+        changed |= ui.add(egui::DragValue::new(&mut parts[2]).speed(0.1).clamp_range(1..=31)).changed();
+        changed |= ui.add(
+            egui::DragValue::new(&mut seconds)
+                .speed(60.0)
+                .clamp_range(0.0..=86_399.0)
+                .custom_formatter(|value, _| {
+                    let value = value as u32;
+                    format!("{:02}:{:02}:{:02}", value / 3600, value / 60 % 60, value % 60)
+                }),
+        ).changed();
+        if ui.button("Now").clicked() {
+            *date = current_date();
+        }
+    }).response.on_hover_text("u_date at time zero of image and video exports, so they don't depend on the wall clock");
+    if changed {
+        *date = [parts[0] as f32, (parts[1] - 1) as f32, parts[2] as f32, seconds];
+    }
+}
+
+fn supersampling_label(factor: u32) -> String {
+    if factor > 1 {
+        format!("{}x", factor)
+    } else {
+        "Off".to_string()
+    }
+}
+
+/// Inspected pixel in every stage, as floats, bytes and hex.
+/// Returns a value picked for a color uniform as (pass, uniform, rgba).
+fn render_pixel_inspector(
+    ui: &mut egui::Ui,
+    inspector: &mut PixelInspector,
+    targets: &[(String, String)],
+) -> Option<(String, String, [f32; 4])> {
+    let Some(readback) = inspector.readback.lock().clone() else {
+        ui.label(egui::RichText::new("Hover the preview to inspect a pixel").small());
+        return None;
+    };
+
+    let [u, v] = readback.uv();
+    ui.horizontal(|ui| {
+        ui.label(
+            egui::RichText::new(format!("Pixel {}, {}  UV {:.4}, {:.4}", readback.pixel[0], readback.pixel[1], u, v))
+                .family(egui::FontFamily::Monospace)
+                .small(),
+        );
+        if inspector.pinned.is_some() && ui.small_button("Unpin").clicked() {
+            inspector.pinned = None;
+        }
+    });
+
+    let mut copy = None;
+    for sample in &readback.samples {
+        ui.horizontal(|ui| {
+            let [r, g, b, _] = sample.rgba.map(component_byte);
+            let (swatch, _) = ui.allocate_exact_size(egui::Vec2::splat(12.0), egui::Sense::hover());
+            ui.painter().rect_filled(swatch, 2.0, egui::Color32::from_rgb(r, g, b));
+
+            if ui.selectable_label(readback.loupe_stage == sample.stage, &sample.stage)
+                .on_hover_text("Show this stage in the loupe")
+                .clicked()
+            {
+                inspector.loupe_stage = Some(sample.stage.clone());
             }
+
+            ui.add_enabled_ui(!targets.is_empty(), |ui| {
+                ui.menu_button("Copy to", |ui| {
+                    for (pass, name) in targets {
+                        let label = if pass == MAIN_PASS_NAME {
+                            name.clone()
+                        } else {
+                            format!("{} / {}", pass, name)
+                        };
+                        if ui.button(label).clicked() {
+                            copy = Some((pass.clone(), name.clone(), sample.rgba));
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }).response.on_disabled_hover_text("No uniforms annotated with @color");
+        });
+
+        let floats = sample.rgba.map(|value| format!("{:.4}", value)).join(" ");
+        let bytes = sample.rgba.map(|value| component_byte(value).to_string()).join(" ");
+        for line in [floats, format!("{}  {}", bytes, color_to_hex(&sample.rgba))] {
+            ui.label(egui::RichText::new(line).family(egui::FontFamily::Monospace).small());
         }
     }
+    copy
+}
+
+/// Min / avg / max of the last benchmark run, per frame and per pass
+fn render_benchmark_result(ui: &mut egui::Ui, result: &BenchmarkResult) {
+    ui.label(egui::RichText::new(format!("{} frames at {}x{}, min / avg / max:", result.frames, result.width, result.height)).small());
+    egui::Grid::new("benchmark_result").num_columns(2).show(ui, |ui| {
+        ui.label(egui::RichText::new("Frame").small());
+        ui.label(egui::RichText::new(result.frame.to_string()).family(egui::FontFamily::Monospace).small());
+        ui.end_row();
+        for (name, stats) in &result.passes {
+            ui.label(egui::RichText::new(format!("{} (GPU)", name)).small());
+            ui.label(egui::RichText::new(stats.to_string()).family(egui::FontFamily::Monospace).small());
+            ui.end_row();
+        }
+    });
+    if result.gpu_wrapped {
+        ui.label(egui::RichText::new("⚠ Frames over 4.3 s: GPU times may have wrapped around").color(egui::Color32::YELLOW).small());
+    }
+}
+
+/// Render target format picker; float formats keep values outside 0..1
+fn render_format_combo(ui: &mut egui::Ui, id_source: impl std::hash::Hash, format: &mut TargetFormat) {
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(format.label())
+        .show_ui(ui, |ui| {
+            for option in TargetFormat::ALL {
+                ui.selectable_value(format, option, option.label());
+            }
+        });
 }
 
 // Helper function to render uniform controls (DRY principle)
 fn render_uniform_controls(
     ui: &mut egui::Ui, 
+    id_source: &str,
     uniforms: &mut std::collections::HashMap<String, UniformInfo>,
     gl: &glow::Context,
 ) {
-    let mut uniform_names: Vec<_> = uniforms.keys().cloned().collect();
-    uniform_names.sort();
+    // Keep the shader's declaration order
+    let mut uniform_names: Vec<_> = uniforms.keys()
+        .filter(|name| !is_hidden_uniform(name))
+        .cloned()
+        .collect();
+    uniform_names.sort_by_key(|name| (uniforms[name].order, name.clone()));
 
+    // Groups appear where their first member is declared
+    let mut sections: Vec<(Option<String>, Vec<String>)> = Vec::new();
     for name in uniform_names {
-        // Skip built-in and auto-injected uniforms
-        if name == "u_resolution" || name == "u_time" || name == "u_mainPass" {
-            continue;
-        }
-        
-        if let Some(uniform) = uniforms.get_mut(&name) {
-            ui.vertical(|ui| {
-                ui.label(&name);
-                match &mut uniform.value {
-                    UniformValue::Float(val) => {
-                        ui.add(egui::Slider::new(val, 0.0..=1.0));
-                    }
-                    UniformValue::Vec2(vals) => {
-                        ui.add(egui::Slider::new(&mut vals[0], 0.0..=1.0).text("x"));
-                        ui.add(egui::Slider::new(&mut vals[1], 0.0..=1.0).text("y"));
-                    }
-                    UniformValue::Vec3(vals) => {
-                        ui.add(egui::Slider::new(&mut vals[0], 0.0..=1.0).text("r"));
-                        ui.add(egui::Slider::new(&mut vals[1], 0.0..=1.0).text("g"));
-                        ui.add(egui::Slider::new(&mut vals[2], 0.0..=1.0).text("b"));
-                    }
-                    UniformValue::Vec4(vals) => {
-                        ui.add(egui::Slider::new(&mut vals[0], 0.0..=1.0).text("r"));
-                        ui.add(egui::Slider::new(&mut vals[1], 0.0..=1.0).text("g"));
-                        ui.add(egui::Slider::new(&mut vals[2], 0.0..=1.0).text("b"));
-                        ui.add(egui::Slider::new(&mut vals[3], 0.0..=1.0).text("a"));
+        let group = uniforms[&name].meta.group.clone();
+        match sections.iter_mut().find(|(g, _)| group.is_some() && *g == group) {
+            Some((_, names)) => names.push(name),
+            None => sections.push((group, vec![name])),
+        }
+    }
+
+    let mut canvas_drag_enabled = None;
+
+    ui.push_id(id_source, |ui| {
+        for (group, names) in sections {
+            let mut render_names = |ui: &mut egui::Ui| {
+                for name in &names {
+                    if let Some(uniform) = uniforms.get_mut(name)
+                        && render_uniform(ui, name, uniform, gl)
+                    {
+                        canvas_drag_enabled = Some(name.clone());
                     }
-                    UniformValue::Sampler2D(texture_handle) => {
-                        if let Some(handle) = texture_handle {
-                            ui.label(format!("📷 {}", 
-                                handle.path.file_name()
-                                    .and_then(|n| n.to_str())
-                                    .unwrap_or("texture")));
-                            ui.label(egui::RichText::new(
-                                format!("{}x{}", handle.width, handle.height)
-                            ).small());
-                        } else {
-                            ui.label(egui::RichText::new("No texture loaded").small());
-                        }
-                        
-                        if ui.button("Load Texture...").clicked() {
-                            if let Some(path) = rfd::FileDialog::new()
-                                .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
-                                .pick_file()
-                            {
-                                match file_io::load_texture_from_file(gl, &path) {
-                                    Ok(new_texture) => {
-                                        // Delete old texture if exists
-                                        if let Some(old_handle) = texture_handle.as_ref() {
-                                            if let Some(old_tex) = old_handle.texture_id {
-                                                file_io::delete_texture(gl, old_tex);
-                                            }
-                                        }
-
-                                        *texture_handle = Some(new_texture.clone());
-                                        log::info!("Texture loaded: {}x{}",
-                                            new_texture.width, new_texture.height);
-                                    }
-                                    Err(e) => {
-                                        log::error!("Failed to load texture: {}", e);
-                                    }
-                                }
+                }
+            };
+
+            match group {
+                // The header id only depends on the group name, so the open state survives reloads
+                Some(group) => {
+                    egui::CollapsingHeader::new(egui::RichText::new(&group).strong())
+                        .id_source(("uniform_group", &group))
+                        .default_open(true)
+                        .show(ui, render_names);
+                }
+                None => render_names(ui),
+            }
+        }
+    });
+
+    // Only one uniform per shader follows the canvas pointer
+    if let Some(enabled) = canvas_drag_enabled {
+        for (name, uniform) in uniforms.iter_mut() {
+            uniform.canvas_drag &= *name == enabled;
+        }
+    }
+}
+
+/// Built-in and auto-injected uniforms that have no editor
+fn is_hidden_uniform(name: &str) -> bool {
+    is_builtin_uniform(name) || name == MAIN_PASS_UNIFORM || name == KEYBOARD_UNIFORM || name == PREV_FRAME_UNIFORM
+}
+
+/// Header and editor for one uniform, returns true when canvas dragging was switched on
+fn render_uniform(ui: &mut egui::Ui, name: &str, uniform: &mut UniformInfo, gl: &glow::Context) -> bool {
+    let mut canvas_drag_enabled = false;
+
+    ui.push_id(name, |ui| ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            ui.label(uniform.display_name(name)).on_hover_text(name);
+            if uniform.line > 0 {
+                ui.label(egui::RichText::new(format!("line {}", uniform.line)).small().weak())
+                    .on_hover_text(format!("Declared on line {}", uniform.line));
+            }
+            if !uniform.active && !uniform.injected {
+                ui.label(egui::RichText::new("unused").small().color(egui::Color32::YELLOW))
+                    .on_hover_text("Optimised away by the driver: the shader never reads this uniform, so changes have no effect");
+            }
+            if uniform.uniform_type == UniformType::Vec2
+                && ui.selectable_label(uniform.canvas_drag, "✥")
+                    .on_hover_text("Drag on the preview canvas")
+                    .clicked()
+            {
+                uniform.canvas_drag = !uniform.canvas_drag;
+                canvas_drag_enabled = uniform.canvas_drag;
+            }
+            if uniform.meta.color.is_some() {
+                ui.selectable_value(&mut uniform.color_space, ColorSpace::Srgb, "sRGB")
+                    .on_hover_text("Upload the picked color unchanged");
+                ui.selectable_value(&mut uniform.color_space, ColorSpace::Linear, "Linear")
+                    .on_hover_text("Convert the picked color to linear before upload");
+            }
+            if !matches!(uniform.uniform_type, UniformType::Sampler2D)
+                && ui.small_button("↺").on_hover_text("Reset to default").clicked()
+            {
+                uniform.value = uniform.default_value();
+            }
+        });
+        if uniform.uniform_type == UniformType::Sampler2D {
+            render_sampler_source(ui, &mut uniform.input);
+            if uniform.input.is_some() {
+                render_texture_params(ui, &mut uniform.input_params, false);
+            }
+        }
+        if uniform.input.is_none() {
+            render_value_editor(ui, &mut uniform.value, &uniform.meta, gl);
+        }
+    }));
+
+    canvas_drag_enabled
+}
+
+/// Choose between a texture file and the output of a buffer pass or the Image pass
+fn render_sampler_source(ui: &mut egui::Ui, input: &mut Option<String>) {
+    egui::ComboBox::from_id_source("sampler_source")
+        .selected_text(input.as_deref().unwrap_or("Texture file"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(input, None, "Texture file");
+            for name in BUFFER_NAMES {
+                ui.selectable_value(input, Some(name.to_string()), name);
+            }
+            ui.selectable_value(input, Some(MAIN_PASS_NAME.to_string()), MAIN_PASS_NAME);
+        });
+}
+
+/// Wrap, filter, mipmaps, flip and sRGB of a loaded texture file, applied as soon as they change
+fn render_file_texture_params(ui: &mut egui::Ui, handle: &mut TextureHandle, gl: &glow::Context) {
+    let before = handle.params;
+    render_texture_params(ui, &mut handle.params, true);
+
+    if handle.params.flip_y != before.flip_y || handle.params.srgb != before.srgb {
+        file_io::upload_texture_image(gl, handle);
+    } else if handle.params != before {
+        file_io::apply_texture_params(gl, handle);
+    }
+}
+
+/// Wrap and filter of a sampler; mipmaps, flip and sRGB only exist for texture files
+fn render_texture_params(ui: &mut egui::Ui, params: &mut TextureParams, file: bool) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source("texture_wrap")
+            .selected_text(params.wrap.label())
+            .width(70.0)
+            .show_ui(ui, |ui| {
+                for wrap in TextureWrap::ALL {
+                    ui.selectable_value(&mut params.wrap, wrap, wrap.label());
+                }
+            });
+        egui::ComboBox::from_id_source("texture_filter")
+            .selected_text(params.filter.label())
+            .width(70.0)
+            .show_ui(ui, |ui| {
+                for filter in TextureFilter::ALL {
+                    ui.selectable_value(&mut params.filter, filter, filter.label());
+                }
+            });
+    });
+    if file {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut params.mipmaps, "Mipmaps");
+            ui.checkbox(&mut params.flip_y, "Flip Y")
+                .on_hover_text("Put the first image row at the top (v = 1)");
+            ui.checkbox(&mut params.srgb, "sRGB")
+                .on_hover_text("Decode the image to linear values when sampled");
+        });
+    }
+}
+
+/// Editor widgets for a single uniform value
+fn render_value_editor(ui: &mut egui::Ui, value: &mut UniformValue, meta: &UniformMeta, gl: &glow::Context) {
+    match value {
+        UniformValue::Float(val) => {
+            ui.add(float_slider(val, meta));
+        }
+        UniformValue::Vec2(vals) => render_xy_pad(ui, vals, meta),
+        UniformValue::Vec3(vals) if meta.color.is_some() => render_color_editor(ui, vals),
+        UniformValue::Vec4(vals) if meta.color.is_some() => render_color_editor(ui, vals),
+        UniformValue::Vec3(vals) => {
+            ui.add(float_slider(&mut vals[0], meta).text("r"));
+            ui.add(float_slider(&mut vals[1], meta).text("g"));
+            ui.add(float_slider(&mut vals[2], meta).text("b"));
+        }
+        UniformValue::Vec4(vals) => {
+            ui.add(float_slider(&mut vals[0], meta).text("r"));
+            ui.add(float_slider(&mut vals[1], meta).text("g"));
+            ui.add(float_slider(&mut vals[2], meta).text("b"));
+            ui.add(float_slider(&mut vals[3], meta).text("a"));
+        }
+        UniformValue::Int(val) if meta.options.is_some() => {
+            let mut index = (*val).max(0) as usize;
+            if render_options_combo(ui, &mut index, meta) {
+                *val = index as i32;
+            }
+        }
+        UniformValue::UInt(val) if meta.options.is_some() => {
+            let mut index = *val as usize;
+            if render_options_combo(ui, &mut index, meta) {
+                *val = index as u32;
+            }
+        }
+        UniformValue::Int(val) => {
+            int_drag_value(ui, val, meta);
+        }
+        UniformValue::IVec2(vals) => render_drag_values(ui, vals, meta),
+        UniformValue::IVec3(vals) => render_drag_values(ui, vals, meta),
+        UniformValue::IVec4(vals) => render_drag_values(ui, vals, meta),
+        UniformValue::UInt(val) => {
+            int_drag_value(ui, val, meta);
+        }
+        UniformValue::UVec2(vals) => render_drag_values(ui, vals, meta),
+        UniformValue::UVec3(vals) => render_drag_values(ui, vals, meta),
+        UniformValue::UVec4(vals) => render_drag_values(ui, vals, meta),
+        UniformValue::Bool(val) => {
+            ui.checkbox(val, "");
+        }
+        UniformValue::BVec2(vals) => render_checkboxes(ui, vals),
+        UniformValue::BVec3(vals) => render_checkboxes(ui, vals),
+        UniformValue::BVec4(vals) => render_checkboxes(ui, vals),
+        UniformValue::Mat2(vals) => render_matrix_grid(ui, vals, 2),
+        UniformValue::Mat3(vals) => {
+            render_matrix_grid(ui, vals, 3);
+            if let Some(params) = render_transform_composer(ui, false) {
+                *vals = params.to_mat3();
+            }
+        }
+        UniformValue::Mat4(vals) => {
+            render_matrix_grid(ui, vals, 4);
+            if let Some(params) = render_transform_composer(ui, true) {
+                *vals = params.to_mat4();
+            }
+        }
+        UniformValue::Sampler2D(texture_handle) => {
+            if let Some(handle) = texture_handle {
+                ui.label(format!("📷 {}", 
+                    handle.path.file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("texture")));
+                ui.label(egui::RichText::new(
+                    format!("{}x{}", handle.width, handle.height)
+                ).small());
+                if handle.image.is_some() {
+                    render_file_texture_params(ui, handle, gl);
+                }
+            } else {
+                ui.label(egui::RichText::new("No texture loaded").small());
+            }
+            
+            if ui.button("Load Texture...").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
+                    .pick_file()
+            {
+                match file_io::load_texture_from_file(gl, &path) {
+                    Ok(mut new_texture) => {
+                        // Delete old texture if exists
+                        if let Some(old_handle) = texture_handle.as_ref() {
+                            if let Some(old_tex) = old_handle.texture_id {
+                                file_io::delete_texture(gl, old_tex);
+                            }
+                            // The sampler keeps its settings for the new image
+                            if old_handle.image.is_some() && old_handle.params != new_texture.params {
+                                new_texture.params = old_handle.params;
+                                file_io::upload_texture_image(gl, &new_texture);
                             }
                         }
+
+                        *texture_handle = Some(new_texture.clone());
+                        log::info!("Texture loaded: {}x{}",
+                            new_texture.width, new_texture.height);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to load texture: {}", e);
                     }
                 }
-            });
+            }
+        }
+        UniformValue::Array(elements) => {
+            egui::CollapsingHeader::new(format!("{} elements", elements.len()))
+                .id_source("array_elements")
+                .show(ui, |ui| {
+                    for (index, element) in elements.iter_mut().enumerate() {
+                        ui.push_id(index, |ui| {
+                            ui.label(egui::RichText::new(format!("[{}]", index)).small());
+                            render_value_editor(ui, element, meta, gl);
+                        });
+                    }
+                });
+        }
+    }
+}
+
+const COMPONENT_LABELS: [&str; 4] = ["x", "y", "z", "w"];
+
+/// Slider honouring `@range` and `@step`
+fn float_slider<'a>(val: &'a mut f32, meta: &UniformMeta) -> egui::Slider<'a> {
+    let slider = egui::Slider::new(val, meta.range_f32());
+    match meta.step {
+        Some(step) => slider.step_by(step),
+        None => slider,
+    }
+}
+
+/// Square pad for vec2 values with precise DragValues below
+fn render_xy_pad(ui: &mut egui::Ui, vals: &mut [f32; 2], meta: &UniformMeta) {
+    let x_range = meta.range_f32();
+    let y_range = meta.range_y_f32();
+
+    let side = ui.available_width().min(160.0);
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(side, side), egui::Sense::click_and_drag());
+
+    if let Some(pos) = response.interact_pointer_pos() {
+        // Y grows upwards, matching shader UV space
+        let uv = egui::vec2(
+            (pos.x - rect.left()) / rect.width(),
+            (rect.bottom() - pos.y) / rect.height(),
+        );
+        *vals = meta.denormalize_xy(uv);
+    }
+
+    let painter = ui.painter_at(rect);
+    let visuals = ui.style().interact(&response);
+    painter.rect(rect, 2.0, ui.visuals().extreme_bg_color, visuals.bg_stroke);
+
+    let grid_stroke = egui::Stroke::new(1.0, ui.visuals().faint_bg_color);
+    for i in 1..4 {
+        let t = i as f32 / 4.0;
+        let x = egui::lerp(rect.left()..=rect.right(), t);
+        let y = egui::lerp(rect.top()..=rect.bottom(), t);
+        painter.line_segment([egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())], grid_stroke);
+        painter.line_segment([egui::pos2(rect.left(), y), egui::pos2(rect.right(), y)], grid_stroke);
+    }
+
+    let u = egui::remap_clamp(vals[0], x_range.clone(), 0.0..=1.0);
+    let v = egui::remap_clamp(vals[1], y_range.clone(), 0.0..=1.0);
+    let point = egui::pos2(
+        egui::lerp(rect.left()..=rect.right(), u),
+        egui::lerp(rect.bottom()..=rect.top(), v),
+    );
+    let crosshair = egui::Stroke::new(1.0, visuals.fg_stroke.color.gamma_multiply(0.4));
+    painter.line_segment([egui::pos2(point.x, rect.top()), egui::pos2(point.x, rect.bottom())], crosshair);
+    painter.line_segment([egui::pos2(rect.left(), point.y), egui::pos2(rect.right(), point.y)], crosshair);
+    painter.circle(point, 5.0, visuals.bg_fill, visuals.fg_stroke);
+
+    let speed = (x_range.end() - x_range.start()).abs().max(f32::EPSILON) / 200.0;
+    ui.horizontal(|ui| {
+        ui.label("x");
+        ui.add(egui::DragValue::new(&mut vals[0]).speed(speed).clamp_range(x_range));
+        ui.label("y");
+        ui.add(egui::DragValue::new(&mut vals[1]).speed(speed).clamp_range(y_range));
+    });
+}
+
+/// Combo box for `@options`, returns true when the selection changed
+fn render_options_combo(ui: &mut egui::Ui, index: &mut usize, meta: &UniformMeta) -> bool {
+    let Some(options) = &meta.options else {
+        return false;
+    };
+
+    let selected_text = options.get(*index).cloned().unwrap_or_else(|| format!("#{}", index));
+    let mut changed = false;
+    egui::ComboBox::from_id_source("options")
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            for (i, option) in options.iter().enumerate() {
+                changed |= ui.selectable_value(index, i, option).changed();
+            }
+        });
+    changed
+}
+
+/// Color picker with hex entry for sRGB-encoded vec3/vec4 values
+fn render_color_editor(ui: &mut egui::Ui, vals: &mut [f32]) {
+    use egui::ecolor::{gamma_from_linear, linear_from_gamma};
+
+    let has_alpha = vals.len() == 4;
+    let alpha = if has_alpha { vals[3] } else { 1.0 };
+
+    ui.horizontal(|ui| {
+        // The picker works on linear colors
+        let mut rgba = egui::Rgba::from_rgba_unmultiplied(
+            linear_from_gamma(vals[0]),
+            linear_from_gamma(vals[1]),
+            linear_from_gamma(vals[2]),
+            alpha,
+        );
+        let alpha_mode = if has_alpha {
+            egui::color_picker::Alpha::OnlyBlend
+        } else {
+            egui::color_picker::Alpha::Opaque
+        };
+        if egui::color_picker::color_edit_button_rgba(ui, &mut rgba, alpha_mode).changed() {
+            let [r, g, b, a] = rgba.to_rgba_unmultiplied();
+            vals[0] = gamma_from_linear(r);
+            vals[1] = gamma_from_linear(g);
+            vals[2] = gamma_from_linear(b);
+            if has_alpha {
+                vals[3] = a;
+            }
+        }
+
+        // Keep the text being typed until the field loses focus
+        let id = ui.id().with("hex");
+        let current = color_to_hex(vals);
+        let mut text = ui.data_mut(|d| d.get_temp::<String>(id)).unwrap_or(current);
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut text)
+                .desired_width(80.0)
+                .font(egui::TextStyle::Monospace),
+        );
+        if response.changed()
+            && let Some(color) = parse_hex_color(&text)
+        {
+            let components = if has_alpha { 4 } else { 3 };
+            vals.copy_from_slice(&color[..components]);
+        }
+        if response.has_focus() {
+            ui.data_mut(|d| d.insert_temp(id, text));
+        } else {
+            ui.data_mut(|d| d.remove::<String>(id));
+        }
+    });
+
+    if has_alpha {
+        ui.add(egui::Slider::new(&mut vals[3], 0.0..=1.0).text("a"));
+    }
+}
+
+fn color_to_hex(vals: &[f32]) -> String {
+    vals.iter()
+        .map(|v| format!("{:02x}", (v.clamp(0.0, 1.0) * 255.0).round() as u8))
+        .fold(String::from("#"), |hex, byte| hex + &byte)
+}
+
+/// Parse `#rgb`, `#rrggbb` or `#rrggbbaa` into 0..1 components
+fn parse_hex_color(text: &str) -> Option<[f32; 4]> {
+    let hex = text.trim().trim_start_matches('#');
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex.to_string(),
+        _ => return None,
+    };
+
+    let mut color = [1.0; 4];
+    for (i, component) in color.iter_mut().enumerate().take(expanded.len() / 2) {
+        let byte = u8::from_str_radix(expanded.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        *component = byte as f32 / 255.0;
+    }
+    Some(color)
+}
+
+/// Integer DragValue honouring `@range` and `@step`; values snap to multiples of the step from the range start
+fn int_drag_value<T: egui::emath::Numeric>(ui: &mut egui::Ui, val: &mut T, meta: &UniformMeta) -> egui::Response {
+    let step = meta.step.map(f64::round).filter(|step| *step > 1.0);
+    let mut drag = egui::DragValue::new(val).speed(step.map_or(0.1, |step| step * 0.1));
+    if let Some((min, max)) = meta.range {
+        drag = drag.clamp_range(min..=max);
+    }
+    let response = ui.add(drag);
+
+    if response.changed()
+        && let Some(step) = step
+    {
+        let base = meta.range.map_or(0.0, |(min, _)| min);
+        let mut snapped = base + ((val.to_f64() - base) / step).round() * step;
+        if let Some((_, max)) = meta.range
+            && snapped > max
+        {
+            snapped -= step;
         }
+        *val = T::from_f64(snapped);
     }
-}
\ No newline at end of file
+    response
+}
+
+/// Integer vector components as a row of DragValues
+fn render_drag_values<T: egui::emath::Numeric>(ui: &mut egui::Ui, vals: &mut [T], meta: &UniformMeta) {
+    ui.horizontal(|ui| {
+        for (val, label) in vals.iter_mut().zip(COMPONENT_LABELS) {
+            ui.label(label);
+            int_drag_value(ui, val, meta);
+        }
+    });
+}
+
+/// Boolean vector components as a row of checkboxes
+fn render_checkboxes(ui: &mut egui::Ui, vals: &mut [bool]) {
+    ui.horizontal(|ui| {
+        for (val, label) in vals.iter_mut().zip(COMPONENT_LABELS) {
+            ui.checkbox(val, label);
+        }
+    });
+}
+
+/// Editable matrix laid out by rows, stored column-major
+fn render_matrix_grid(ui: &mut egui::Ui, vals: &mut [f32], n: usize) {
+    egui::Grid::new("matrix_grid").spacing([4.0, 2.0]).show(ui, |ui| {
+        for row in 0..n {
+            for col in 0..n {
+                ui.add(egui::DragValue::new(&mut vals[col * n + row]).speed(0.01).max_decimals(3));
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// Translate/rotate/scale editor, returns the parameters when they change
+fn render_transform_composer(ui: &mut egui::Ui, is_3d: bool) -> Option<TransformParams> {
+    let id = ui.id().with("transform_params");
+    let mut params: TransformParams = ui.data_mut(|d| *d.get_temp_mut_or_default(id));
+    let components = if is_3d { 3 } else { 2 };
+    let mut changed = false;
+
+    egui::CollapsingHeader::new("Compose from translate/rotate/scale").show(ui, |ui| {
+        egui::Grid::new("transform_grid").spacing([4.0, 2.0]).show(ui, |ui| {
+            ui.label("Translate");
+            for val in &mut params.translate[..components] {
+                changed |= ui.add(egui::DragValue::new(val).speed(0.01)).changed();
+            }
+            ui.end_row();
+
+            ui.label("Rotate");
+            if is_3d {
+                for val in &mut params.rotate {
+                    changed |= ui.add(egui::DragValue::new(val).speed(1.0).suffix("°")).changed();
+                }
+            } else {
+                changed |= ui.add(egui::DragValue::new(&mut params.rotate[2]).speed(1.0).suffix("°")).changed();
+            }
+            ui.end_row();
+
+            ui.label("Scale");
+            for val in &mut params.scale[..components] {
+                changed |= ui.add(egui::DragValue::new(val).speed(0.01)).changed();
+            }
+            ui.end_row();
+        });
+
+        if ui.button("Reset").clicked() {
+            params = TransformParams::default();
+            changed = true;
+        }
+    });
+
+    ui.data_mut(|d| d.insert_temp(id, params));
+    changed.then_some(params)
+}